@@ -0,0 +1,370 @@
+use std::{
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use futures_lite::{AsyncRead, AsyncReadExt as _};
+use tokio::io::{AsyncWrite, AsyncWriteExt as _};
+
+use crate::{archive::ExtractReport, error::Error, interrupt};
+
+/// Limits enforced while extracting untrusted archives.
+#[derive(Debug, Clone)]
+pub struct ExtractLimits {
+    /// Maximum number of bytes extracted from an input, nested archives
+    /// included.
+    pub max_size: u64,
+    /// Maximum number of entries in an input, nested archives included.
+    pub max_entries: usize,
+    /// Maximum ratio between the bytes extracted from an archive and its size.
+    pub max_ratio: u64,
+}
+
+impl ExtractLimits {
+    /// Archives smaller than this when extracted are never rejected for their
+    /// compression ratio, as tiny repetitive test data compresses very well.
+    const RATIO_GRACE: u64 = 16 << 20;
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 8 << 30,
+            max_entries: 100_000,
+            max_ratio: 1000,
+        }
+    }
+}
+
+/// Tracks the extraction of one archive against [`ExtractLimits`].
+pub struct Budget<'a> {
+    limits: &'a ExtractLimits,
+    report: &'a mut ExtractReport,
+    archive_path: &'a Path,
+    archive_size: u64,
+    extracted: u64,
+}
+
+impl<'a> Budget<'a> {
+    pub fn new(
+        limits: &'a ExtractLimits,
+        report: &'a mut ExtractReport,
+        archive_path: &'a Path,
+        archive_size: u64,
+    ) -> Self {
+        Self {
+            limits,
+            report,
+            archive_path,
+            archive_size,
+            extracted: 0,
+        }
+    }
+
+    pub fn report(&mut self) -> &mut ExtractReport {
+        self.report
+    }
+
+    /// Accounts for a new entry.
+    pub fn add_entry(&mut self) -> Result<(), Error> {
+        self.report.entries += 1;
+        self.report.progress.entries(self.report.entries);
+        if self.report.entries > self.limits.max_entries {
+            return Err(Error::TooManyEntries(self.limits.max_entries));
+        }
+        Ok(())
+    }
+
+    /// Copies an entry from `reader` to `writer`, counting the bytes actually
+    /// read rather than trusting sizes recorded in the archive.
+    pub fn copy(&mut self, reader: &mut impl Read, writer: &mut impl Write) -> anyhow::Result<()> {
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let len = match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.consume(len as u64)?;
+            writer.write_all(&buf[..len])?;
+        }
+    }
+
+    /// Like [`Budget::copy`], for async readers and writers.
+    pub async fn copy_async(
+        &mut self,
+        reader: &mut (impl AsyncRead + Unpin),
+        writer: &mut (impl AsyncWrite + Unpin),
+    ) -> anyhow::Result<()> {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let len = match reader.read(&mut buf).await {
+                Ok(0) => return Ok(writer.flush().await?),
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.consume(len as u64)?;
+            writer.write_all(&buf[..len]).await?;
+        }
+    }
+
+    /// Wraps `reader`, accounting for the bytes read through it like
+    /// [`Budget::copy`] does.
+    pub fn reader<R: Read>(&mut self, reader: R) -> Metered<'_, 'a, R> {
+        Metered {
+            budget: self,
+            inner: reader,
+        }
+    }
+
+    fn consume(&mut self, len: u64) -> Result<(), Error> {
+        interrupt::check()?;
+        self.extracted += len;
+        self.report.size += len;
+        self.report.progress.inc(len);
+
+        if self.report.size > self.limits.max_size {
+            return Err(Error::TooLarge(self.limits.max_size));
+        }
+        if self.extracted > ExtractLimits::RATIO_GRACE
+            && self.extracted / self.archive_size.max(1) > self.limits.max_ratio
+        {
+            return Err(Error::CompressionRatio {
+                path: self.archive_path.to_path_buf(),
+                limit: self.limits.max_ratio,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A reader whose bytes count against a [`Budget`], see [`Budget::reader`].
+pub struct Metered<'b, 'a, R> {
+    budget: &'b mut Budget<'a>,
+    inner: R,
+}
+
+impl<R: Read> Read for Metered<'_, '_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.budget.consume(len as u64).map_err(io::Error::other)?;
+        Ok(len)
+    }
+}
+
+/// Turns the path of an archive entry into a relative path that stays inside
+/// the extraction directory.
+pub fn entry_path(name: &Path) -> Result<PathBuf, Error> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => return Err(Error::PathTraversal(name.to_path_buf())),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(Error::AbsolutePath(name.to_path_buf()));
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// Creates the parent directories of `path`, an entry path relative to `dest`,
+/// and returns the parent as it resolves on disk, relative to `dest`.
+///
+/// `dest` must be canonical. Entries whose parent resolves outside of `dest`
+/// through a symlink are rejected.
+pub fn resolve_parent(dest: &Path, path: &Path) -> anyhow::Result<PathBuf> {
+    let parent = dest.join(path.parent().unwrap_or(Path::new("")));
+    std::fs::create_dir_all(&parent)?;
+
+    Ok(check_parent(dest, path, parent.canonicalize()?)?)
+}
+
+/// Like [`resolve_parent`], with async file system calls.
+pub async fn resolve_parent_async(dest: &Path, path: &Path) -> anyhow::Result<PathBuf> {
+    let parent = dest.join(path.parent().unwrap_or(Path::new("")));
+    tokio::fs::create_dir_all(&parent).await?;
+
+    Ok(check_parent(
+        dest,
+        path,
+        tokio::fs::canonicalize(&parent).await?,
+    )?)
+}
+
+fn check_parent(dest: &Path, path: &Path, resolved: PathBuf) -> Result<PathBuf, Error> {
+    match resolved.strip_prefix(dest) {
+        Ok(relative) => Ok(relative.to_path_buf()),
+        Err(_) => Err(Error::EscapingSymlink {
+            path: path.to_path_buf(),
+            target: resolved,
+        }),
+    }
+}
+
+/// Returns the file on disk that a hard link entry to `source` links to.
+///
+/// Hard links to symlinks are rejected, as the copied link would resolve
+/// relative to its new location rather than the one it was checked at.
+pub fn hard_link_source(dest: &Path, source: &Path) -> anyhow::Result<PathBuf> {
+    let path = entry_path(source)?;
+    let parent = dest.join(path.parent().unwrap_or(Path::new("")));
+    let parent = check_parent(dest, &path, parent.canonicalize()?)?;
+    let source = dest.join(parent).join(path.file_name().unwrap_or_default());
+
+    if std::fs::symlink_metadata(&source)?.is_symlink() {
+        Err(Error::HardLinkedSymlink(path))?;
+    }
+    Ok(source)
+}
+
+/// Removes an earlier entry at `target` if it is a symlink, so that writing a
+/// file there replaces the link instead of following it.
+pub fn remove_symlink(target: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(target) {
+        Ok(metadata) if metadata.is_symlink() => std::fs::remove_file(target),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Like [`remove_symlink`], with async file system calls.
+pub async fn remove_symlink_async(target: &Path) -> io::Result<()> {
+    match tokio::fs::symlink_metadata(target).await {
+        Ok(metadata) if metadata.is_symlink() => tokio::fs::remove_file(target).await,
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a symlink at `path`, whose parent resolves to `parent` relative
+/// to the extraction directory, does not point outside of it.
+///
+/// `..` components are only allowed at the start of `target`, as they could
+/// otherwise climb out of a directory reached through another symlink.
+pub fn check_symlink(path: &Path, parent: &Path, target: &Path) -> Result<(), Error> {
+    let escaping = || Error::EscapingSymlink {
+        path: path.to_path_buf(),
+        target: target.to_path_buf(),
+    };
+
+    let mut depth = parent.components().count();
+    let mut descended = false;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => {
+                depth += 1;
+                descended = true;
+            }
+            Component::CurDir => {}
+            Component::ParentDir if !descended => {
+                depth = depth.checked_sub(1).ok_or_else(escaping)?;
+            }
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(escaping());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read as _},
+        path::Path,
+    };
+
+    use tempfile::TempDir;
+
+    use super::{Budget, ExtractLimits, check_symlink, entry_path, resolve_parent};
+    use crate::{archive::ExtractReport, error::Error};
+
+    #[test]
+    fn entry_paths() {
+        assert_eq!(
+            entry_path(Path::new("./data/1.in")).unwrap(),
+            Path::new("data/1.in")
+        );
+        assert!(matches!(
+            entry_path(Path::new("/etc/passwd")),
+            Err(Error::AbsolutePath(_))
+        ));
+        assert!(matches!(
+            entry_path(Path::new("data/../../evil")),
+            Err(Error::PathTraversal(_))
+        ));
+    }
+
+    #[test]
+    fn symlinks() {
+        let link = Path::new("data/link");
+        let parent = Path::new("data");
+
+        assert!(check_symlink(link, parent, Path::new("../1.in")).is_ok());
+        assert!(check_symlink(link, parent, Path::new("./sub/1.in")).is_ok());
+        for target in ["../../etc", "/etc/passwd", "sub/../../.."] {
+            assert!(
+                matches!(
+                    check_symlink(link, parent, Path::new(target)),
+                    Err(Error::EscapingSymlink { .. })
+                ),
+                "{target}"
+            );
+        }
+    }
+
+    #[test]
+    fn symlinked_parents() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let dest = dir.path().canonicalize()?;
+
+        assert_eq!(
+            resolve_parent(&dest, Path::new("data/1.in"))?,
+            Path::new("data")
+        );
+
+        #[cfg(unix)]
+        {
+            let outside = TempDir::new()?;
+            std::os::unix::fs::symlink(outside.path(), dest.join("evil"))?;
+            let err = resolve_parent(&dest, Path::new("evil/1.in")).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(Error::EscapingSymlink { .. })
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn limits() {
+        let limits = ExtractLimits {
+            max_size: 32 << 20,
+            max_entries: 1,
+            max_ratio: 1000,
+        };
+        let mut report = ExtractReport::default();
+        let mut budget = Budget::new(&limits, &mut report, Path::new("a.zip"), 1 << 10);
+
+        budget.add_entry().unwrap();
+        assert!(matches!(budget.add_entry(), Err(Error::TooManyEntries(1))));
+
+        let mut bomb = io::repeat(0).take(20 << 20);
+        let err = budget.copy(&mut bomb, &mut io::sink()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::CompressionRatio { .. })
+        ));
+
+        let mut report = ExtractReport::default();
+        let mut budget = Budget::new(&limits, &mut report, Path::new("a.zip"), 1 << 30);
+        let mut large = io::repeat(0).take(40 << 20);
+        let err = budget.copy(&mut large, &mut io::sink()).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(Error::TooLarge(_))));
+    }
+}
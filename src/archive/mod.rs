@@ -0,0 +1,694 @@
+mod guard;
+mod stream;
+
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    io::{self, Read},
+    mem,
+    path::{Path, PathBuf},
+};
+
+use async_walkdir::WalkDir;
+use async_zip::tokio::read::fs::ZipFileReader;
+use encoding_rs::Encoding;
+use flate2::read::GzDecoder;
+use futures_lite::AsyncReadExt as _;
+use tar::EntryType;
+use tokio::{
+    fs::{self, File},
+    io::AsyncReadExt,
+    task,
+};
+use tokio_stream::StreamExt;
+use xz2::read::XzDecoder;
+
+use self::guard::Budget;
+pub use self::{
+    guard::ExtractLimits,
+    stream::{StreamedEntry, ZipEntries, list_zip, read_zip},
+};
+use crate::{encoding::NameDecoder, error::Error, junk::JunkFilter, progress::Progress};
+
+/// Archive formats accepted as input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// Enough bytes to cover the `ustar` magic of a tar header.
+    const SNIFF_LEN: u64 = 512;
+
+    const EXTENSIONS: [(&str, Self); 8] = [
+        (".zip", Self::Zip),
+        (".tar", Self::Tar),
+        (".tar.gz", Self::TarGz),
+        (".tgz", Self::TarGz),
+        (".tar.xz", Self::TarXz),
+        (".txz", Self::TarXz),
+        (".tar.zst", Self::TarZst),
+        (".tzst", Self::TarZst),
+    ];
+
+    fn match_extension(path: &Path) -> Option<(&'static str, Self)> {
+        let name = path
+            .file_name()
+            .and_then(OsStr::to_str)?
+            .to_ascii_lowercase();
+
+        Self::EXTENSIONS
+            .into_iter()
+            .find(|(ext, _)| name.ends_with(ext) && name.len() > ext.len())
+    }
+
+    /// Guesses the format from the file name of `path`.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        Self::match_extension(path).map(|(_, format)| format)
+    }
+
+    /// Returns the file name of `path` without its archive extension, e.g.
+    /// `A` for `A.tar.gz`.
+    pub fn strip_extension(path: &Path) -> &OsStr {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        match (Self::match_extension(path), name.to_str()) {
+            (Some((ext, _)), Some(name)) => OsStr::new(&name[..name.len() - ext.len()]),
+            _ => name,
+        }
+    }
+
+    /// Guesses the format from the leading bytes of an archive.
+    pub fn from_magic(header: &[u8]) -> Option<Self> {
+        match header {
+            [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] => Some(Self::Zip),
+            [0x1f, 0x8b, ..] => Some(Self::TarGz),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Self::TarXz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::TarZst),
+            _ if header.get(257..262) == Some(b"ustar") => Some(Self::Tar),
+            _ => None,
+        }
+    }
+
+    /// Detects the format of the archive at `path`, preferring magic bytes over
+    /// the file name.
+    pub async fn detect(path: &Path) -> io::Result<Option<Self>> {
+        let mut header = Vec::new();
+        File::open(path)
+            .await?
+            .take(Self::SNIFF_LEN)
+            .read_to_end(&mut header)
+            .await?;
+
+        Ok(Self::from_magic(&header).or_else(|| Self::from_extension(path)))
+    }
+}
+
+/// Options for unpacking an input into the working area.
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// How many levels of archives nested inside the input are expanded.
+    pub max_depth: usize,
+    /// Entries matching this filter are not extracted.
+    pub junk: JunkFilter,
+    pub limits: ExtractLimits,
+    /// Encoding of ZIP entry names that are not valid UTF-8, guessed if unset.
+    pub zip_encoding: Option<&'static Encoding>,
+    /// Read ZIP inputs while packaging instead of extracting them first.
+    pub stream: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 2,
+            junk: JunkFilter::default(),
+            limits: ExtractLimits::default(),
+            zip_encoding: None,
+            stream: false,
+        }
+    }
+}
+
+/// What happened to an input while unpacking it.
+#[derive(Debug, Default)]
+pub struct ExtractReport {
+    /// Paths of junk entries that were skipped.
+    pub dropped: Vec<PathBuf>,
+    /// Number of entries extracted so far.
+    pub entries: usize,
+    /// Number of bytes extracted so far.
+    pub size: u64,
+    /// Legacy encodings used to decode ZIP entry names.
+    pub decoded: Vec<&'static Encoding>,
+    /// Where entries and bytes are counted, and warnings are printed.
+    pub progress: Progress,
+}
+
+/// Extracts the archive at `archive_path` into `dest` and normalizes the
+/// resulting layout, see [`normalize`].
+pub async fn extract_all(
+    archive_path: &Path,
+    dest: &Path,
+    options: &ExtractOptions,
+    progress: Progress,
+) -> anyhow::Result<ExtractReport> {
+    progress.bytes("extracting");
+    let mut report = ExtractReport {
+        progress,
+        ..ExtractReport::default()
+    };
+    extract(archive_path, dest, options, &mut report).await?;
+    normalize(dest, options, &mut report).await?;
+
+    Ok(report)
+}
+
+/// Normalizes the layout of an unpacked input in `dir`.
+///
+/// A single top-level wrapper directory is stripped, and nested archives are
+/// expanded into a directory named after their stem, next to the archive,
+/// up to `options.max_depth` levels deep.
+pub async fn normalize(
+    dir: &Path,
+    options: &ExtractOptions,
+    report: &mut ExtractReport,
+) -> anyhow::Result<()> {
+    flatten_wrapper(dir).await?;
+
+    let mut pending = find_archives(dir).await?;
+    for _ in 0..options.max_depth {
+        if pending.is_empty() {
+            break;
+        }
+
+        let mut next = Vec::new();
+        for nested in pending {
+            let target = nested.with_file_name(ArchiveFormat::strip_extension(&nested));
+            fs::create_dir_all(&target).await?;
+            extract(&nested, &target, options, report).await?;
+            fs::remove_file(&nested).await?;
+            flatten_wrapper(&target).await?;
+
+            next.extend(find_archives(&target).await?);
+        }
+        pending = next;
+    }
+
+    for nested in pending {
+        report.progress.println(format_args!(
+            "Nested archive {} exceeds the depth limit, kept as is",
+            nested.strip_prefix(dir).unwrap_or(&nested).display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Moves the content of `dir/wrapper/` into `dir` while `wrapper` is the only
+/// entry of `dir`.
+async fn flatten_wrapper(dir: &Path) -> io::Result<()> {
+    loop {
+        let mut entries = fs::read_dir(dir).await?;
+        let (Some(wrapper), None) = (entries.next_entry().await?, entries.next_entry().await?)
+        else {
+            return Ok(());
+        };
+        if !wrapper.file_type().await?.is_dir() {
+            return Ok(());
+        }
+
+        // `dir` has no other entries, so the temporary name is always free,
+        // even if the wrapper contains an entry with its own name.
+        let wrapper_path = dir.join(".yarusto-wrapper");
+        fs::rename(wrapper.path(), &wrapper_path).await?;
+
+        let mut children = fs::read_dir(&wrapper_path).await?;
+        while let Some(child) = children.next_entry().await? {
+            fs::rename(child.path(), dir.join(child.file_name())).await?;
+        }
+        fs::remove_dir(&wrapper_path).await?;
+    }
+}
+
+async fn find_archives(dir: &Path) -> crate::error::Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+
+    let mut entries = WalkDir::new(dir);
+    while let Some(entry) = entries.try_next().await? {
+        let path = entry.path();
+        if ArchiveFormat::from_extension(&path).is_some() && entry.file_type().await?.is_file() {
+            archives.push(path);
+        }
+    }
+
+    Ok(archives)
+}
+
+/// Extracts the archive at `archive_path` into `dest`, skipping junk entries.
+///
+/// ZIP archives are read with async I/O, tar archives are decompressed on a
+/// blocking thread.
+async fn extract(
+    archive_path: &Path,
+    dest: &Path,
+    options: &ExtractOptions,
+    report: &mut ExtractReport,
+) -> anyhow::Result<()> {
+    let format = ArchiveFormat::detect(archive_path)
+        .await?
+        .ok_or_else(|| Error::UnsupportedArchive(archive_path.to_path_buf()))?;
+    let dest = fs::canonicalize(dest).await?;
+    let archive_size = fs::metadata(archive_path).await?.len();
+
+    if format == ArchiveFormat::Zip {
+        let mut budget = Budget::new(&options.limits, report, archive_path, archive_size);
+        return extract_zip(archive_path, &dest, options, &mut budget).await;
+    }
+
+    let file = File::open(archive_path).await?.into_std().await;
+    let archive_path = archive_path.to_path_buf();
+    let limits = options.limits.clone();
+    let junk = options.junk.clone();
+    let mut owned_report = mem::take(report);
+    let (result, owned_report) = task::spawn_blocking(move || {
+        let mut budget = Budget::new(&limits, &mut owned_report, &archive_path, archive_size);
+        let budget = &mut budget;
+        let result = match format {
+            ArchiveFormat::Tar => extract_tar(file, &dest, &junk, budget),
+            ArchiveFormat::TarGz => extract_tar(GzDecoder::new(file), &dest, &junk, budget),
+            ArchiveFormat::TarXz => extract_tar(XzDecoder::new(file), &dest, &junk, budget),
+            ArchiveFormat::TarZst => zstd::Decoder::new(file)
+                .map_err(Into::into)
+                .and_then(|decoder| extract_tar(decoder, &dest, &junk, budget)),
+            ArchiveFormat::Zip => unreachable!("ZIP archives are extracted asynchronously"),
+        };
+        (result, owned_report)
+    })
+    .await?;
+    *report = owned_report;
+
+    result
+}
+
+enum EntryKind {
+    File,
+    Dir,
+    Symlink(PathBuf),
+    HardLink(PathBuf),
+}
+
+async fn extract_zip(
+    archive_path: &Path,
+    dest: &Path,
+    options: &ExtractOptions,
+    budget: &mut Budget<'_>,
+) -> anyhow::Result<()> {
+    /// File type bits of a symlink in Unix permissions.
+    const S_IFLNK: u16 = 0o120000;
+
+    let archive = ZipFileReader::new(archive_path).await?;
+    let raw_names: Vec<_> = archive
+        .file()
+        .entries()
+        .iter()
+        .map(|entry| entry.filename().as_bytes())
+        .collect();
+    let names = decode_zip_names(&raw_names, options.zip_encoding, budget.report());
+
+    for (index, name) in names.iter().enumerate() {
+        let Some(name) = check_entry(dest, name, &options.junk, budget)? else {
+            continue;
+        };
+        let parent = guard::resolve_parent_async(dest, &name).await?;
+        let target = dest
+            .join(&parent)
+            .join(name.file_name().expect("Checked entry"));
+
+        let mut reader = archive.reader_with_entry(index).await?;
+        let entry = reader.entry();
+        let is_symlink = entry
+            .unix_permissions()
+            .is_some_and(|mode| mode & 0o170000 == S_IFLNK);
+
+        if is_symlink {
+            let mut link = String::new();
+            (&mut reader).take(4096).read_to_string(&mut link).await?;
+            let link = PathBuf::from(link);
+            guard::check_symlink(&name, &parent, &link)?;
+            task::spawn_blocking(move || symlink(&link, &target)).await??;
+        } else if raw_names[index].ends_with(b"/") {
+            fs::create_dir_all(&target).await?;
+        } else {
+            let crc32 = entry.crc32();
+            guard::remove_symlink_async(&target).await?;
+            budget
+                .copy_async(&mut reader, &mut File::create(&target).await?)
+                .await?;
+            if reader.compute_hash() != crc32 {
+                Err(Error::Checksum(name))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes the names of all entries in a ZIP archive at once, so that legacy
+/// encodings are guessed from all of them.
+fn decode_zip_names(
+    raw_names: &[&[u8]],
+    encoding: Option<&'static Encoding>,
+    report: &mut ExtractReport,
+) -> Vec<PathBuf> {
+    let decoder = NameDecoder::new(raw_names.iter().copied(), encoding);
+    if let Some(encoding) = decoder.legacy_encoding() {
+        report.decoded.push(encoding);
+    }
+
+    raw_names
+        .iter()
+        .map(|name| PathBuf::from(decoder.decode(name).into_owned()))
+        .collect()
+}
+
+fn extract_tar(
+    reader: impl Read,
+    dest: &Path,
+    junk: &JunkFilter,
+    budget: &mut Budget,
+) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+
+        let link_name = || -> io::Result<PathBuf> {
+            entry
+                .link_name()?
+                .map(Cow::into_owned)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing link name"))
+        };
+        let kind = match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous => EntryKind::File,
+            EntryType::Directory => EntryKind::Dir,
+            EntryType::Symlink => EntryKind::Symlink(link_name()?),
+            EntryType::Link => EntryKind::HardLink(link_name()?),
+            // Devices, FIFOs and metadata entries have no use in test data.
+            _ => continue,
+        };
+
+        extract_entry(dest, &name, kind, &mut entry, junk, budget)?;
+    }
+
+    Ok(())
+}
+
+/// Accounts for an archive entry named `name` and turns it into a safe relative
+/// path, or returns `None` if the entry is skipped.
+fn check_entry(
+    dest: &Path,
+    name: &Path,
+    junk: &JunkFilter,
+    budget: &mut Budget,
+) -> anyhow::Result<Option<PathBuf>> {
+    budget.add_entry()?;
+
+    let name = guard::entry_path(name)?;
+    if name.file_name().is_none() {
+        return Ok(None);
+    }
+    if junk.is_junk(&name) {
+        budget.report().dropped.push(dest.join(name));
+        return Ok(None);
+    }
+
+    Ok(Some(name))
+}
+
+/// Writes an archive entry named `name` into `dest`, which must be canonical.
+fn extract_entry(
+    dest: &Path,
+    name: &Path,
+    kind: EntryKind,
+    reader: &mut impl Read,
+    junk: &JunkFilter,
+    budget: &mut Budget,
+) -> anyhow::Result<()> {
+    let Some(name) = check_entry(dest, name, junk, budget)? else {
+        return Ok(());
+    };
+
+    let parent = guard::resolve_parent(dest, &name)?;
+    let target = dest
+        .join(&parent)
+        .join(name.file_name().expect("Checked entry"));
+
+    match kind {
+        EntryKind::File => {
+            guard::remove_symlink(&target)?;
+            budget.copy(reader, &mut std::fs::File::create(&target)?)?;
+        }
+        EntryKind::Dir => std::fs::create_dir_all(&target)?,
+        EntryKind::Symlink(link) => {
+            guard::check_symlink(&name, &parent, &link)?;
+            symlink(&link, &target)?;
+        }
+        EntryKind::HardLink(source) => {
+            std::fs::hard_link(guard::hard_link_source(dest, &source)?, &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+/// Symlinks need extra privileges on Windows, so the link is stored as a plain
+/// file holding its target, like Git does.
+#[cfg(not(unix))]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::fs::write(link, original.as_os_str().as_encoded_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write as _, path::Path};
+
+    #[cfg(unix)]
+    use tar::EntryType;
+    use tempfile::TempDir;
+    use tokio::fs;
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    #[cfg(unix)]
+    use super::EntryKind;
+    use super::{ArchiveFormat, ExtractOptions, ExtractReport, extract, flatten_wrapper};
+    #[cfg(unix)]
+    use crate::error::Error;
+
+    #[test]
+    fn from_extension() {
+        let cases = [
+            ("a.zip", Some(ArchiveFormat::Zip)),
+            ("a.ZIP", Some(ArchiveFormat::Zip)),
+            ("a.tar", Some(ArchiveFormat::Tar)),
+            ("a.tar.gz", Some(ArchiveFormat::TarGz)),
+            ("a.tgz", Some(ArchiveFormat::TarGz)),
+            ("a.tar.xz", Some(ArchiveFormat::TarXz)),
+            ("config.tar.zst", Some(ArchiveFormat::TarZst)),
+            ("a.in", None),
+            ("tar", None),
+            (".zip", None),
+        ];
+        for (name, format) in cases {
+            assert_eq!(
+                ArchiveFormat::from_extension(Path::new(name)),
+                format,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn strip_extension() {
+        assert_eq!(ArchiveFormat::strip_extension(Path::new("A.zip")), "A");
+        assert_eq!(ArchiveFormat::strip_extension(Path::new("d/A.TAR.GZ")), "A");
+        assert_eq!(ArchiveFormat::strip_extension(Path::new("A.in")), "A.in");
+    }
+
+    #[test]
+    fn from_magic() {
+        assert_eq!(
+            ArchiveFormat::from_magic(b"PK\x03\x04rest"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+            Some(ArchiveFormat::TarZst)
+        );
+
+        let mut tar_header = [0; 512];
+        tar_header[257..262].copy_from_slice(b"ustar");
+        assert_eq!(
+            ArchiveFormat::from_magic(&tar_header),
+            Some(ArchiveFormat::Tar)
+        );
+
+        assert_eq!(ArchiveFormat::from_magic(b"time: 1"), None);
+    }
+
+    #[tokio::test]
+    async fn flatten_wrapper_dirs() -> std::io::Result<()> {
+        let dir = TempDir::new()?;
+        let wrapper = dir.path().join("outer/problemA");
+        fs::create_dir_all(wrapper.join("problemA")).await?;
+        fs::write(wrapper.join("config.yaml"), "").await?;
+        fs::write(wrapper.join("problemA/1.in"), "").await?;
+
+        flatten_wrapper(dir.path()).await?;
+
+        assert!(
+            fs::metadata(dir.path().join("config.yaml"))
+                .await?
+                .is_file()
+        );
+        assert!(
+            fs::metadata(dir.path().join("problemA/1.in"))
+                .await?
+                .is_file()
+        );
+        assert!(fs::metadata(dir.path().join("outer")).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn extract_zip() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let archive_path = dir.path().join("a.zip");
+        let mut writer = ZipWriter::new(std::fs::File::create(&archive_path)?);
+        writer.add_directory("data/", SimpleFileOptions::default())?;
+        writer.start_file("data/1.in", SimpleFileOptions::default())?;
+        writer.write_all(&b"1 2\n".repeat(1000))?;
+        writer.start_file("__MACOSX/._1.in", SimpleFileOptions::default())?;
+        writer.finish()?;
+
+        let dest = dir.path().join("out");
+        fs::create_dir(&dest).await?;
+        let mut report = ExtractReport::default();
+        extract(
+            &archive_path,
+            &dest,
+            &ExtractOptions::default(),
+            &mut report,
+        )
+        .await?;
+
+        assert_eq!(
+            fs::read(dest.join("data/1.in")).await?,
+            b"1 2\n".repeat(1000)
+        );
+        assert_eq!(report.entries, 3);
+        assert_eq!(report.size, 4000);
+        assert_eq!(report.dropped.len(), 1);
+
+        Ok(())
+    }
+
+    /// Writes a tar archive of `(name, kind)` entries to `path`.
+    #[cfg(unix)]
+    fn write_tar(path: &Path, entries: &[(&str, EntryKind)]) -> std::io::Result<()> {
+        let mut builder = tar::Builder::new(std::fs::File::create(path)?);
+        for (name, kind) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_size(0);
+            match kind {
+                EntryKind::File => {
+                    header.set_size(5);
+                    builder.append_data(&mut header, name, &b"PWNED"[..])?;
+                }
+                EntryKind::Dir => {
+                    header.set_entry_type(EntryType::Directory);
+                    builder.append_data(&mut header, name, std::io::empty())?;
+                }
+                EntryKind::Symlink(target) => {
+                    header.set_entry_type(EntryType::Symlink);
+                    builder.append_link(&mut header, name, target)?;
+                }
+                EntryKind::HardLink(source) => {
+                    header.set_entry_type(EntryType::Link);
+                    builder.append_link(&mut header, name, source)?;
+                }
+            }
+        }
+        builder.finish()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn escaping_links() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let secret = dir.path().join("secret");
+        fs::write(&secret, "secret").await?;
+        let dest = dir.path().join("x/out");
+        fs::create_dir_all(&dest).await?;
+
+        // `a/b/l` stays inside `out`, but a copy of it at the root would not.
+        let archive_path = dir.path().join("hard-link.tar");
+        write_tar(
+            &archive_path,
+            &[
+                ("a/b/l", EntryKind::Symlink("../../secret".into())),
+                ("l2", EntryKind::HardLink("a/b/l".into())),
+                ("l2", EntryKind::File),
+            ],
+        )?;
+        let err = extract(
+            &archive_path,
+            &dest,
+            &ExtractOptions::default(),
+            &mut ExtractReport::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::HardLinkedSymlink(_))
+        ));
+        assert_eq!(fs::read_to_string(&secret).await?, "secret");
+
+        // Files replace earlier symlinks instead of writing through them.
+        let archive_path = dir.path().join("overwrite.tar");
+        let dest = dir.path().join("overwrite");
+        fs::create_dir(&dest).await?;
+        write_tar(
+            &archive_path,
+            &[
+                ("2.in", EntryKind::File),
+                ("1.in", EntryKind::Symlink("2.in".into())),
+                ("1.in", EntryKind::File),
+                ("3.in", EntryKind::HardLink("2.in".into())),
+            ],
+        )?;
+        extract(
+            &archive_path,
+            &dest,
+            &ExtractOptions::default(),
+            &mut ExtractReport::default(),
+        )
+        .await?;
+        assert!(!fs::symlink_metadata(dest.join("1.in")).await?.is_symlink());
+        assert_eq!(fs::read(dest.join("3.in")).await?, b"PWNED");
+
+        Ok(())
+    }
+}
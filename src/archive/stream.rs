@@ -0,0 +1,291 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use zip::{ZipArchive, result::ZipResult};
+
+use super::{
+    ArchiveFormat, ExtractLimits, ExtractOptions, ExtractReport, decode_zip_names,
+    guard::{self, Budget},
+};
+use crate::error::Error;
+
+/// An entry of a ZIP archive that is read while packaging, see [`list_zip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamedEntry {
+    Dir,
+    /// A file, by its index in the archive.
+    File(usize),
+}
+
+/// Lists the entries of the ZIP archive at `archive_path` without extracting
+/// it, applying the same checks, junk filter and name decoding as extraction.
+///
+/// Wrapper directories are stripped from the returned paths. Nested archives
+/// can't be expanded without extracting them, so they are kept as is.
+pub fn list_zip(
+    archive_path: &Path,
+    options: &ExtractOptions,
+    report: &mut ExtractReport,
+) -> anyhow::Result<Vec<(PathBuf, StreamedEntry)>> {
+    let file = File::open(archive_path)?;
+    let archive_size = file.metadata()?.len();
+    let mut archive = ZipArchive::new(file)?;
+    let raw_names = (0..archive.len())
+        .map(|index| Ok(archive.by_index_raw(index)?.name_raw().to_vec()))
+        .collect::<ZipResult<Vec<_>>>()?;
+    let raw_names: Vec<_> = raw_names.iter().map(Vec::as_slice).collect();
+    let names = decode_zip_names(&raw_names, options.zip_encoding, report);
+    let mut budget = Budget::new(&options.limits, report, archive_path, archive_size);
+
+    let mut entries = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        budget.add_entry()?;
+
+        let path = guard::entry_path(name)?;
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        if options.junk.is_junk(&path) {
+            budget.report().dropped.push(path);
+            continue;
+        }
+
+        let entry = archive.by_index_raw(index)?;
+        if entry.is_symlink() {
+            Err(Error::StreamedSymlink(path))?;
+        } else if entry.is_dir() {
+            entries.push((path, StreamedEntry::Dir));
+        } else {
+            entries.push((path, StreamedEntry::File(index)));
+        }
+    }
+
+    strip_wrapper(&mut entries);
+    add_parents(&mut entries);
+
+    for (path, entry) in &entries {
+        if *entry != StreamedEntry::Dir && ArchiveFormat::from_extension(path).is_some() {
+            budget.report().progress.println(format_args!(
+                "Nested archive {} is not expanded when streaming, kept as is",
+                path.display()
+            ));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Strips the first component of all paths while they share it, like
+/// [`super::flatten_wrapper`] does on disk.
+fn strip_wrapper(entries: &mut Vec<(PathBuf, StreamedEntry)>) {
+    loop {
+        let Some(wrapper) = entries
+            .iter()
+            .find(|(path, _)| path.components().count() > 1)
+            .and_then(|(path, _)| path.components().next())
+        else {
+            return;
+        };
+        let wrapper = PathBuf::from(wrapper.as_os_str());
+
+        let wrapped = entries.iter().all(|(path, entry)| {
+            path.starts_with(&wrapper) && (path != &wrapper || *entry == StreamedEntry::Dir)
+        });
+        if !wrapped {
+            return;
+        }
+
+        entries.retain(|(path, _)| path != &wrapper);
+        for (path, _) in entries.iter_mut() {
+            *path = path.strip_prefix(&wrapper).unwrap_or(path).to_path_buf();
+        }
+    }
+}
+
+/// Adds the parent directories that aren't stored explicitly, as they exist on
+/// disk when the archive is extracted.
+fn add_parents(entries: &mut Vec<(PathBuf, StreamedEntry)>) {
+    let mut dirs: BTreeSet<_> = entries
+        .iter()
+        .filter(|(_, entry)| *entry == StreamedEntry::Dir)
+        .map(|(path, _)| path.clone())
+        .collect();
+    let parents: Vec<_> = entries
+        .iter()
+        .flat_map(|(path, _)| path.ancestors().skip(1))
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect();
+    for parent in parents {
+        if dirs.insert(parent.clone()) {
+            entries.push((parent, StreamedEntry::Dir));
+        }
+    }
+}
+
+/// Entries of a ZIP archive read while packaging, see [`read_zip`].
+pub struct ZipEntries<'a> {
+    archive: ZipArchive<File>,
+    budget: Budget<'a>,
+}
+
+impl ZipEntries<'_> {
+    /// Returns the size of the file at `index` and a reader over its content,
+    /// which fails if the content doesn't match the size.
+    pub fn file(&mut self, index: usize) -> anyhow::Result<(u64, impl Read + '_)> {
+        let entry = self.archive.by_index(index)?;
+        let size = entry.size();
+        let reader = Exact {
+            inner: self.budget.reader(entry),
+            remaining: size,
+        };
+        Ok((size, reader))
+    }
+}
+
+/// Opens the ZIP archive at `archive_path` to read the entries listed by
+/// [`list_zip`], enforcing `limits` on the bytes read.
+pub fn read_zip<T>(
+    archive_path: &Path,
+    limits: &ExtractLimits,
+    f: impl FnOnce(&mut ZipEntries<'_>) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let file = File::open(archive_path)?;
+    let archive_size = file.metadata()?.len();
+    let mut report = ExtractReport::default();
+
+    f(&mut ZipEntries {
+        archive: ZipArchive::new(file)?,
+        budget: Budget::new(limits, &mut report, archive_path, archive_size),
+    })
+}
+
+/// Reads exactly `remaining` bytes, as tar headers carry the size of an entry
+/// before its content.
+///
+/// The inner reader is read to its end, so that the ZIP reader checks the
+/// CRC of the entry.
+struct Exact<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for Exact<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return match self.inner.read(&mut [0])? {
+                0 => Ok(0),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "entry is longer than its recorded size",
+                )),
+            };
+        }
+
+        let max = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let len = self.inner.read(&mut buf[..max])?;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "entry is shorter than its recorded size",
+            ));
+        }
+        self.remaining -= len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Read as _, Write as _},
+        path::PathBuf,
+    };
+
+    use tempfile::tempdir;
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    use super::{StreamedEntry, add_parents, read_zip, strip_wrapper};
+    use crate::archive::ExtractLimits;
+
+    #[test]
+    fn strip_wrappers() {
+        let mut entries = vec![
+            (PathBuf::from("outer"), StreamedEntry::Dir),
+            (PathBuf::from("outer/A"), StreamedEntry::Dir),
+            (PathBuf::from("outer/A/config.yaml"), StreamedEntry::File(2)),
+            (PathBuf::from("outer/A/A/1.in"), StreamedEntry::File(3)),
+        ];
+        strip_wrapper(&mut entries);
+        assert_eq!(
+            entries,
+            [
+                (PathBuf::from("config.yaml"), StreamedEntry::File(2)),
+                (PathBuf::from("A/1.in"), StreamedEntry::File(3)),
+            ]
+        );
+
+        let mut entries = vec![
+            (PathBuf::from("1.in"), StreamedEntry::File(0)),
+            (PathBuf::from("data/2.in"), StreamedEntry::File(1)),
+        ];
+        let expected = entries.clone();
+        strip_wrapper(&mut entries);
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn parents() {
+        let mut entries = vec![
+            (PathBuf::from("data"), StreamedEntry::Dir),
+            (PathBuf::from("data/1/1.in"), StreamedEntry::File(1)),
+            (PathBuf::from("data/2/2.in"), StreamedEntry::File(2)),
+        ];
+        add_parents(&mut entries);
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(
+            entries,
+            [
+                (PathBuf::from("data"), StreamedEntry::Dir),
+                (PathBuf::from("data/1"), StreamedEntry::Dir),
+                (PathBuf::from("data/1/1.in"), StreamedEntry::File(1)),
+                (PathBuf::from("data/2"), StreamedEntry::Dir),
+                (PathBuf::from("data/2/2.in"), StreamedEntry::File(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn checksum() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("a.zip");
+        let mut writer = ZipWriter::new(fs::File::create(&path)?);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("1.in", options)?;
+        writer.write_all(b"hello world")?;
+        writer.finish()?;
+
+        let read = || {
+            read_zip(&path, &ExtractLimits::default(), |zip| {
+                let mut content = Vec::new();
+                zip.file(0)?.1.read_to_end(&mut content)?;
+                Ok(content)
+            })
+        };
+        assert_eq!(read()?, b"hello world");
+
+        let mut bytes = fs::read(&path)?;
+        let pos = bytes.windows(5).position(|w| w == b"hello").unwrap();
+        bytes[pos] = b'H';
+        fs::write(&path, bytes)?;
+        assert!(read().is_err());
+        Ok(())
+    }
+}
@@ -1,14 +1,224 @@
-use std::path::PathBuf;
+use std::{
+    num::{NonZeroU32, NonZeroUsize, ParseIntError},
+    path::PathBuf,
+    str::FromStr,
+};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use encoding_rs::Encoding;
+use size::Size;
+
+use crate::{
+    archive::{ExtractLimits, ExtractOptions},
+    compression::Compression,
+    discovery::Layout,
+    encoding,
+    error::Error,
+    model::{
+        format::ConfigFormat,
+        types::{duration::CustomDuration, judge::JudgeType, memory_size::CustomSize},
+    },
+    package::OutputFormat,
+    template::{self, NameTemplate},
+};
 
 #[derive(Debug, Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 pub struct Cli {
-    /// Input directory for ZIP archives
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub convert: ConvertArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Convert inputs into packages, the default without a subcommand
+    Convert(Box<ConvertArgs>),
+    /// Check the detached signature of a package
+    Verify(VerifyArgs),
+    /// Print the JSON Schema of the configs written to packages
+    Schema,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /// Input archive (.zip, .tar, .tar.gz, .tar.xz, .tar.zst), a directory searched
+    /// recursively for archives, or a problem directory with a config file
     #[arg(default_value = ".")]
     pub input: PathBuf,
-    /// Output directory for tarballs (.tar.zst)
+    /// Output directory for packages
     #[arg(short, long, default_value = "./out")]
     pub output: PathBuf,
+    /// Format of generated packages
+    #[arg(short, long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+    /// Name of packages without extension, may use `{stem}` of the input,
+    /// `{contest}` for the searched directory and the problem `{id}`
+    #[arg(long, value_name = "TEMPLATE", default_value = "config")]
+    pub name: NameTemplate,
+    /// Replace existing packages
+    #[arg(long)]
+    pub force: bool,
+    /// Format of the configs written to packages
+    #[arg(long, value_enum, default_value_t)]
+    pub config_format: ConfigFormat,
+    /// Top-level directory inside packages
+    #[arg(long, value_name = "NAME", default_value = "config", value_parser = parse_root)]
+    pub root: String,
+    /// Only convert archives matching this glob, may be repeated
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+    /// Skip archives and directories matching this glob, may be repeated
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+    /// Output layout for archives found in the input directory
+    #[arg(long, value_enum, default_value_t)]
+    pub layout: Layout,
+    /// Maximum nesting depth of archives expanded inside an input
+    #[arg(long, value_name = "N", default_value_t = ExtractOptions::default().max_depth)]
+    pub max_depth: usize,
+    /// Also drop entries matching this glob while extracting, may be repeated
+    #[arg(long, value_name = "GLOB", conflicts_with = "keep_junk")]
+    pub junk: Vec<String>,
+    /// Keep OS junk files like `__MACOSX/`, `._*`, `.DS_Store` and `Thumbs.db`
+    #[arg(long)]
+    pub keep_junk: bool,
+    /// Maximum total size extracted from an input
+    #[arg(long, value_name = "SIZE", default_value = "8GiB")]
+    pub max_size: Size,
+    /// Maximum number of entries in an input
+    #[arg(long, value_name = "N", default_value_t = ExtractLimits::default().max_entries)]
+    pub max_entries: usize,
+    /// Maximum compression ratio of an archive
+    #[arg(long, value_name = "RATIO", default_value_t = ExtractLimits::default().max_ratio)]
+    pub max_ratio: u64,
+    /// Encoding of non-UTF-8 file names in ZIP archives, e.g. `gbk` or
+    /// `shift_jis` [default: guessed]
+    #[arg(long, value_name = "ENCODING", value_parser = encoding::parse_label)]
+    pub zip_encoding: Option<&'static Encoding>,
+    /// Normalize test data: convert CRLF to LF, strip BOMs and add missing
+    /// final newlines
+    #[arg(long)]
+    pub normalize: bool,
+    /// Also trim trailing spaces and tabs when normalizing test data
+    #[arg(long, requires = "normalize")]
+    pub trim_trailing: bool,
+    /// Stream ZIP inputs into the tarball without extracting them to disk.
+    /// Nested archives are kept as is
+    #[arg(long, conflicts_with = "normalize")]
+    pub stream: bool,
+    /// Zstandard compression level of `tar-zst` packages, up to 22. Negative
+    /// levels are faster [default: 1]
+    #[arg(long, value_name = "LEVEL", allow_negative_numbers = true, value_parser = parse_level)]
+    pub level: Option<i32>,
+    /// Number of zstd worker threads per `tar-zst` package, 0 compresses on
+    /// the packaging thread [default: 0]
+    #[arg(long, value_name = "N", value_parser = parse_workers)]
+    pub workers: Option<u32>,
+    /// Enable long-distance matching in `tar-zst` packages with a window of
+    /// 2^WINDOW_LOG bytes. Windows above 27 need `zstd --long=WINDOW_LOG` to
+    /// decompress
+    #[arg(long, value_name = "WINDOW_LOG", value_parser = parse_window_log,
+          num_args = 0..=1, require_equals = true, default_missing_value = "27")]
+    pub long: Option<u32>,
+    /// Number of inputs converted concurrently
+    #[arg(short, long, value_name = "N", default_value = "1")]
+    pub jobs: NonZeroUsize,
+    /// Time limit of configs generated for inputs without a config file, e.g.
+    /// `1s` or `500ms`
+    #[arg(long, value_name = "DURATION")]
+    pub time: Option<CustomDuration>,
+    /// Memory limit of generated configs, e.g. `256MiB`
+    #[arg(long, value_name = "SIZE")]
+    pub memory: Option<CustomSize>,
+    /// Score of generated configs
+    #[arg(long, value_name = "N")]
+    pub score: Option<NonZeroU32>,
+    /// Judge of generated configs
+    #[arg(long, value_enum, requires_ifs = [
+        ("special-judge", "judge_program"),
+        ("interactive", "judge_program"),
+    ])]
+    pub judge: Option<JudgeKind>,
+    /// Checker or interactor of generated configs, relative to the package
+    /// root
+    #[arg(long, value_name = "PATH", requires = "judge")]
+    pub judge_program: Option<PathBuf>,
+    /// Sign packages with this Ed25519 private key (PKCS#8 PEM), writing a
+    /// detached signature to `<PACKAGE>.sig`
+    #[arg(long, value_name = "KEY")]
+    pub sign: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum JudgeKind {
+    Classic,
+    SpecialJudge,
+    Interactive,
+}
+
+impl JudgeKind {
+    /// Returns the judge using `program` as checker or interactor, which the
+    /// classic judge doesn't take.
+    pub fn with_program(self, program: Option<PathBuf>) -> Result<JudgeType, Error> {
+        Ok(match (self, program) {
+            (Self::Classic, None) => JudgeType::Classic,
+            (Self::Classic, Some(_)) => Err(Error::UnusedJudgeProgram)?,
+            (Self::SpecialJudge, program) => JudgeType::SpecialJudge {
+                checker: program.unwrap_or_default(),
+            },
+            (Self::Interactive, program) => JudgeType::Interactive {
+                interactor: program.unwrap_or_default(),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Package to verify
+    pub package: PathBuf,
+    /// Ed25519 public key of the signer (PEM)
+    #[arg(long, value_name = "KEY")]
+    pub pubkey: PathBuf,
+    /// Detached signature [default: <PACKAGE>.sig]
+    #[arg(long, value_name = "FILE")]
+    pub signature: Option<PathBuf>,
+}
+
+fn parse_root(value: &str) -> Result<String, String> {
+    template::check_name(value).map_err(|e| e.to_string())?;
+    Ok(value.to_owned())
+}
+
+fn parse_level(value: &str) -> Result<i32, String> {
+    parse_compression(value, |level| Compression {
+        level,
+        ..Compression::default()
+    })
+}
+
+fn parse_workers(value: &str) -> Result<u32, String> {
+    parse_compression(value, |workers| Compression {
+        workers,
+        ..Compression::default()
+    })
+}
+
+fn parse_window_log(value: &str) -> Result<u32, String> {
+    parse_compression(value, |window_log| Compression {
+        long_window_log: Some(window_log),
+        ..Compression::default()
+    })
+}
+
+/// Parses a number and validates it as part of [`Compression`].
+fn parse_compression<T: FromStr<Err = ParseIntError> + Copy>(
+    value: &str,
+    compression: impl FnOnce(T) -> Compression,
+) -> Result<T, String> {
+    let value = value.parse().map_err(|e: ParseIntError| e.to_string())?;
+    compression(value).validate().map_err(|e| e.to_string())?;
+    Ok(value)
 }
@@ -0,0 +1,160 @@
+use std::{io::Write, ops::RangeInclusive};
+
+use zstd::{
+    Encoder,
+    zstd_safe::zstd_sys::{self, ZSTD_cParameter},
+};
+
+use crate::error::Error;
+
+/// Zstandard settings for generated packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compression {
+    /// Compression level, negative levels trade ratio for speed.
+    pub level: i32,
+    /// Number of worker threads, `0` compresses on the packaging thread.
+    pub workers: u32,
+    /// Base-2 logarithm of the long-distance matching window, disabled if
+    /// unset.
+    ///
+    /// Windows above [`Compression::DEFAULT_WINDOW_LOG`] must be allowed
+    /// explicitly when decompressing, e.g. with `zstd --long=<N>`.
+    pub long_window_log: Option<u32>,
+}
+
+impl Compression {
+    /// Largest window decoders accept by default, 128 MiB.
+    pub const DEFAULT_WINDOW_LOG: u32 = 27;
+
+    /// Levels supported by the linked zstd library.
+    pub fn levels() -> RangeInclusive<i32> {
+        zstd::compression_level_range()
+    }
+
+    /// Numbers of workers supported by the linked zstd library.
+    pub fn workers() -> RangeInclusive<u32> {
+        unsigned(bounds(ZSTD_cParameter::ZSTD_c_nbWorkers))
+    }
+
+    /// Window logs supported by the linked zstd library.
+    pub fn window_logs() -> RangeInclusive<u32> {
+        unsigned(bounds(ZSTD_cParameter::ZSTD_c_windowLog))
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        check("compression level", self.level, Self::levels())?;
+        check("number of workers", self.workers, Self::workers())?;
+        if let Some(window_log) = self.long_window_log {
+            check("long-distance window log", window_log, Self::window_logs())?;
+        }
+        Ok(())
+    }
+
+    /// Creates an encoder writing to `writer` with these settings.
+    pub fn encoder<W: Write>(&self, writer: W) -> anyhow::Result<Encoder<'static, W>> {
+        self.validate()?;
+
+        let mut encoder = Encoder::new(writer, self.level)?;
+        if self.workers > 0 {
+            encoder.multithread(self.workers)?;
+        }
+        if let Some(window_log) = self.long_window_log {
+            encoder.long_distance_matching(true)?;
+            encoder.window_log(window_log)?;
+        }
+        Ok(encoder)
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            workers: 0,
+            long_window_log: None,
+        }
+    }
+}
+
+fn bounds(parameter: ZSTD_cParameter) -> RangeInclusive<i32> {
+    // SAFETY: `ZSTD_cParam_getBounds` only looks up constants.
+    let bounds = unsafe { zstd_sys::ZSTD_cParam_getBounds(parameter) };
+    bounds.lowerBound..=bounds.upperBound
+}
+
+fn unsigned(range: RangeInclusive<i32>) -> RangeInclusive<u32> {
+    let (start, end) = range.into_inner();
+    u32::try_from(start).unwrap_or(0)..=u32::try_from(end).unwrap_or(0)
+}
+
+fn check<T>(name: &'static str, value: T, range: RangeInclusive<T>) -> Result<(), Error>
+where
+    T: PartialOrd + ToString,
+{
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(Error::OutOfRange {
+            name,
+            value: value.to_string(),
+            min: range.start().to_string(),
+            max: range.end().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+    use crate::error::Error;
+
+    #[test]
+    fn validate() {
+        assert!(Compression::default().validate().is_ok());
+
+        let max = Compression {
+            level: *Compression::levels().end(),
+            workers: 4,
+            long_window_log: Some(Compression::DEFAULT_WINDOW_LOG),
+        };
+        assert!(max.validate().is_ok());
+
+        let invalid = [
+            Compression {
+                level: 100,
+                ..Compression::default()
+            },
+            Compression {
+                workers: u32::MAX,
+                ..Compression::default()
+            },
+            Compression {
+                long_window_log: Some(5),
+                ..Compression::default()
+            },
+        ];
+        for compression in invalid {
+            assert!(matches!(
+                compression.validate(),
+                Err(Error::OutOfRange { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn compress() -> anyhow::Result<()> {
+        let data = b"1 2\n".repeat(10_000);
+        let compression = Compression {
+            level: 19,
+            workers: 2,
+            long_window_log: Some(20),
+        };
+
+        let mut encoder = compression.encoder(Vec::new())?;
+        std::io::Write::write_all(&mut encoder, &data)?;
+        let compressed = encoder.finish()?;
+
+        assert_eq!(zstd::decode_all(&compressed[..])?, data);
+        Ok(())
+    }
+}
@@ -1,165 +1,1110 @@
 use std::{
     borrow::Cow,
-    ffi::OsStr,
-    io,
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Read},
+    mem,
     path::{Path, PathBuf},
     str,
+    sync::Arc,
 };
 
-use async_walkdir::WalkDir;
+use async_walkdir::{Filtering, WalkDir};
+use encoding_rs::UTF_8;
+use serde::{Deserialize, de::IgnoredAny};
 use tempfile::TempDir;
-use tokio::{
-    fs::{self, File},
-    io::AsyncWriteExt,
-};
+use tokio::{fs, task};
 use tokio_stream::StreamExt;
-use zip::ZipArchive;
 
-use crate::model::{
-    cases_config::CasesConfig, config::Config, raw::config1::ConfigData as Config1,
+use crate::{
+    archive::{
+        self, ArchiveFormat, ExtractLimits, ExtractOptions, ExtractReport, StreamedEntry,
+        ZipEntries,
+    },
+    discovery::{config_rank, is_problem_dir},
+    encoding,
+    error::Error,
+    interrupt::Interruptible,
+    model::{
+        cases_config::CasesConfig,
+        config::Config,
+        format::ConfigFormat,
+        manifest::{HashReader, MANIFEST_FILE, Manifest, ManifestFile},
+        raw::{
+            config1::ConfigData as Config1,
+            generated::{ConfigOverrides, GeneratedConfig},
+        },
+    },
+    normalize::{self, NormalizeOptions},
+    package::{self, Package, PackageOptions},
+    progress::Progress,
 };
 
+/// Where the files of an input are read from.
+enum Input {
+    /// Unpacked into a temporary working directory, removed on drop.
+    Extracted { _dir: TempDir },
+    /// Read from a ZIP archive while packaging, see [`archive::list_zip`].
+    Zip {
+        path: PathBuf,
+        limits: ExtractLimits,
+    },
+}
+
+/// Where the content of a package entry comes from.
+#[derive(Clone)]
+enum Content {
+    Dir,
+    /// A file in the working directory.
+    File(PathBuf),
+    /// A file of the input ZIP archive, by its index.
+    Zipped(usize),
+    /// Generated content, such as converted configs.
+    Generated(Vec<u8>),
+}
+
+/// Converts an input into a package.
+///
+/// Renaming and converting only change the in-memory list of package entries,
+/// file contents are read once when the package is written.
 pub struct Converter {
-    config_paths: Vec<PathBuf>,
-    temp_dir: TempDir,
+    /// Shared with blocking tasks that read the input.
+    input: Arc<Input>,
+    /// Entries of the package, keyed by their path below its root directory.
+    entries: BTreeMap<PathBuf, Content>,
+    /// Paths of the SAST-OJ configs, converted or kept as they are.
+    configs: BTreeSet<PathBuf>,
+    /// Problem ids from converted configs, by problem directory.
+    problem_ids: BTreeMap<PathBuf, String>,
+    progress: Progress,
 }
 
 impl Converter {
-    pub async fn with_input_path(input_path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        async fn inner(input_path: &Path) -> anyhow::Result<Converter> {
-            let zip_file = find_zip_file(input_path).await?;
+    fn new(input: Input, entries: BTreeMap<PathBuf, Content>, progress: Progress) -> Self {
+        let converter = Self {
+            input: Arc::new(input),
+            entries,
+            configs: BTreeSet::new(),
+            problem_ids: BTreeMap::new(),
+            progress,
+        };
+        converter.progress.println(format_args!(
+            "Found {} config files",
+            converter.config_paths().len()
+        ));
+        converter
+    }
+
+    /// Creates a converter from an archive or a problem directory.
+    ///
+    /// ZIP archives are streamed if `options.stream` is set.
+    pub async fn with_input_path(
+        input_path: impl AsRef<Path>,
+        options: &ExtractOptions,
+        progress: Progress,
+    ) -> anyhow::Result<Self> {
+        async fn inner(
+            input_path: &Path,
+            options: &ExtractOptions,
+            progress: Progress,
+        ) -> anyhow::Result<Converter> {
+            if is_problem_dir(input_path).await? {
+                Converter::with_problem_dir(input_path, options, progress).await
+            } else if options.stream
+                && ArchiveFormat::detect(input_path).await? == Some(ArchiveFormat::Zip)
+            {
+                Converter::with_zip_stream(input_path, options, progress).await
+            } else {
+                Converter::with_archive(input_path, options, progress).await
+            }
+        }
+        inner(input_path.as_ref(), options, progress).await
+    }
+
+    pub async fn with_archive(
+        archive_path: impl AsRef<Path>,
+        options: &ExtractOptions,
+        progress: Progress,
+    ) -> anyhow::Result<Self> {
+        async fn inner(
+            archive_path: &Path,
+            options: &ExtractOptions,
+            progress: Progress,
+        ) -> anyhow::Result<Converter> {
+            let temp_dir = TempDir::new()?;
 
-            if let Some(path) = zip_file {
-                let temp_dir = TempDir::new()?;
+            let report =
+                archive::extract_all(archive_path, temp_dir.path(), options, progress).await?;
+            print_report(&report, temp_dir.path());
+
+            Converter::with_extracted(temp_dir, report.progress).await
+        }
+        inner(archive_path.as_ref(), options, progress).await
+    }
 
-                let config_paths = extract_config_file(path, &temp_dir).await?;
+    /// Creates a converter from a plain problem directory, e.g. a checkout of a
+    /// problem repository.
+    ///
+    /// The directory is copied into the working area, so the source tree is
+    /// never modified. `.git` directories and other Git files are skipped.
+    pub async fn with_problem_dir(
+        problem_dir: impl AsRef<Path>,
+        options: &ExtractOptions,
+        progress: Progress,
+    ) -> anyhow::Result<Self> {
+        async fn inner(
+            problem_dir: &Path,
+            options: &ExtractOptions,
+            progress: Progress,
+        ) -> anyhow::Result<Converter> {
+            let temp_dir = TempDir::new()?;
 
-                Ok(Converter {
-                    config_paths,
-                    temp_dir,
+            progress.bytes("copying");
+            let mut report = ExtractReport {
+                progress,
+                ..ExtractReport::default()
+            };
+            copy_dir(problem_dir, temp_dir.path(), options, &mut report).await?;
+            archive::normalize(temp_dir.path(), options, &mut report).await?;
+            print_report(&report, temp_dir.path());
+
+            Converter::with_extracted(temp_dir, report.progress).await
+        }
+        inner(problem_dir.as_ref(), options, progress).await
+    }
+
+    /// Creates a converter that reads a ZIP archive while packaging, without
+    /// extracting it to disk.
+    ///
+    /// Nested archives are kept as is, and test data can't be normalized.
+    pub async fn with_zip_stream(
+        archive_path: impl AsRef<Path>,
+        options: &ExtractOptions,
+        progress: Progress,
+    ) -> anyhow::Result<Self> {
+        async fn inner(
+            archive_path: &Path,
+            options: &ExtractOptions,
+            progress: Progress,
+        ) -> anyhow::Result<Converter> {
+            progress.bytes("listing");
+            let (listed, report) = task::spawn_blocking({
+                let archive_path = archive_path.to_path_buf();
+                let options = options.clone();
+                move || {
+                    let mut report = ExtractReport {
+                        progress,
+                        ..ExtractReport::default()
+                    };
+                    archive::list_zip(&archive_path, &options, &mut report)
+                        .map(|listed| (listed, report))
+                }
+            })
+            .await??;
+            let entries = listed
+                .into_iter()
+                .map(|(path, entry)| match entry {
+                    StreamedEntry::Dir => (path, Content::Dir),
+                    StreamedEntry::File(index) => (path, Content::Zipped(index)),
                 })
-            } else {
-                Err(io::Error::from(io::ErrorKind::NotFound))?
-            }
+                .collect();
+            print_report(&report, Path::new(""));
+
+            let input = Input::Zip {
+                path: archive_path.to_path_buf(),
+                limits: options.limits.clone(),
+            };
+            Ok(Converter::new(input, entries, report.progress))
         }
-        inner(input_path.as_ref()).await
+        inner(archive_path.as_ref(), options, progress).await
+    }
+
+    async fn with_extracted(temp_dir: TempDir, progress: Progress) -> anyhow::Result<Self> {
+        let entries = index_dir(temp_dir.path()).await?;
+        Ok(Self::new(
+            Input::Extracted { _dir: temp_dir },
+            entries,
+            progress,
+        ))
     }
 
-    pub async fn rename(&self) -> crate::error::Result<&Self> {
-        fn map_ext(orig: &str) -> Cow<'_, str> {
-            match orig {
-                "in" => Cow::Borrowed(orig),
-                _ => Cow::Owned(String::from("out")),
+    /// Returns the config file of each directory, preferring YAML sources
+    /// over previously converted configs, see [`config_rank`].
+    fn config_paths(&self) -> Vec<PathBuf> {
+        let mut configs: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(_, content)| !matches!(content, Content::Dir))
+            .filter_map(|(path, _)| Some((path.parent()?, config_rank(path)?, path)))
+            .collect();
+        configs.sort();
+        configs.dedup_by_key(|(dir, ..)| *dir);
+        configs.into_iter().map(|(.., path)| path.clone()).collect()
+    }
+
+    /// Renames test data to `<N>.in` / `<N>.out`, see [`renamed`].
+    pub fn rename(&mut self) -> &mut Self {
+        self.progress.steps("renaming", self.entries.len());
+        self.entries = mem::take(&mut self.entries)
+            .into_iter()
+            .inspect(|_| self.progress.inc(1))
+            .map(|(path, content)| match content {
+                Content::Dir => (path, content),
+                _ => (renamed(&path).unwrap_or(path), content),
+            })
+            .collect();
+        self
+    }
+
+    /// Normalizes line endings of test data, see [`normalize::normalize`].
+    pub async fn normalize(&self, options: &NormalizeOptions) -> anyhow::Result<&Self> {
+        let mut changed = Vec::new();
+
+        let test_data: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(path, _)| {
+                path.extension()
+                    .is_some_and(|ext| ext == "in" || ext == "out" || ext == "ans")
+            })
+            .collect();
+        self.progress.steps("normalizing", test_data.len());
+
+        for (path, content) in test_data {
+            self.progress.inc(1);
+            let file_path = match content {
+                Content::File(file_path) => file_path.clone(),
+                Content::Zipped(_) => anyhow::bail!("test data can't be normalized when streaming"),
+                Content::Dir | Content::Generated(_) => continue,
+            };
+
+            let options = options.clone();
+            let outcome =
+                task::spawn_blocking(move || normalize::normalize_file(&file_path, &options))
+                    .await??;
+
+            match outcome {
+                None => self.progress.println(format_args!(
+                    "Skipped normalizing binary file {}",
+                    path.display()
+                )),
+                Some(changes) if !changes.is_empty() => changed.push((path, changes)),
+                Some(_) => {}
             }
         }
 
-        let mut entries = WalkDir::new(&self.temp_dir);
-        while let Some(entry) = entries.try_next().await? {
-            let path = entry.path();
+        if !changed.is_empty() {
+            self.progress
+                .println(format_args!("Normalized {} files:", changed.len()));
+            for (path, changes) in changed {
+                self.progress
+                    .println(format_args!("  {}: {changes}", path.display()));
+            }
+        }
 
-            if let (Some(stem), Some(ext)) = (path.file_stem(), path.extension()) {
-                if let (stem, ext @ (b"in" | b"out" | b"ans")) =
-                    (stem.as_encoded_bytes(), ext.as_encoded_bytes())
-                {
-                    let digits = if let Some(pos) = stem.iter().rposition(|b| !b.is_ascii_digit()) {
-                        &stem[pos + 1..]
-                    } else {
-                        stem
-                    };
+        Ok(self)
+    }
 
-                    // SAFETY: `digits` is composed of ASCII digits only
-                    let new_stem = unsafe { str::from_utf8_unchecked(digits) };
+    /// Converts the config files to `format`, or generates a config from the
+    /// test data with `overrides` if there are none, see [`GeneratedConfig`].
+    ///
+    /// Configs may be written in YAML, JSON or TOML. Configs that already are
+    /// a [`CasesConfig`] are validated, and kept as they are if they are
+    /// written in `format`.
+    pub async fn convert(
+        &mut self,
+        format: ConfigFormat,
+        overrides: &ConfigOverrides,
+    ) -> anyhow::Result<&mut Self> {
+        let config_paths = self.config_paths();
+        if config_paths.is_empty() {
+            return self.generate(format, overrides);
+        }
+        self.progress.steps("converting", config_paths.len());
 
-                    // SAFETY: `ext` is one of `"in"` / `"out"` / `"ans"`, which are all ASCII-only
-                    let new_ext = map_ext(unsafe { str::from_utf8_unchecked(ext) });
-                    let new_path = path.with_file_name(format!("{new_stem}.{new_ext}"));
-                    fs::rename(&path, new_path).await?;
+        for config_path in config_paths {
+            self.progress.inc(1);
+            let bytes = self.read(&config_path).await?;
+            let (text, source_encoding) = encoding::decode_text(&bytes);
+            if source_encoding != UTF_8 {
+                self.progress.println(format_args!(
+                    "Transcoded {} from {} to UTF-8",
+                    config_path.display(),
+                    source_encoding.name()
+                ));
+            }
+
+            let target_path = config_path.with_file_name(format.file_name());
+            let source_format =
+                ConfigFormat::from_path(&config_path).expect("Config files have a known format");
+            let target = if source_format.parse::<Shape>(&text)?.task.is_some() {
+                let target: CasesConfig = source_format.parse(&text)?;
+                if config_path == target_path {
+                    if source_encoding != UTF_8 {
+                        let text = text.into_owned().into_bytes();
+                        self.entries
+                            .insert(target_path.clone(), Content::Generated(text));
+                    }
+                    self.drop_other_configs(&target_path).await?;
+                    self.configs.insert(target_path);
+                    continue;
+                }
+                target
+            } else {
+                // TODO: Erase the concrete type here.
+                let raw: Config1 = source_format.parse(&text)?;
+                let config: Box<dyn Config> = Box::new(raw);
+                if let Some(id) = config.id() {
+                    let problem = config_path.parent().unwrap_or(Path::new(""));
+                    self.problem_ids.insert(problem.to_path_buf(), id);
                 }
+                CasesConfig::try_from(config)?
+            };
+
+            self.drop_other_configs(&target_path).await?;
+            self.entries.insert(
+                target_path.clone(),
+                Content::Generated(format.serialize(&target)?.into_bytes()),
+            );
+            self.configs.insert(target_path);
+        }
+
+        Ok(self)
+    }
+
+    /// Drops the SAST-OJ configs next to `target_path` in other formats, so
+    /// that a problem ships a single config. Raw configs are kept.
+    async fn drop_other_configs(&mut self, target_path: &Path) -> anyhow::Result<()> {
+        let others: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|path| path.parent() == target_path.parent() && *path != target_path)
+            .filter(|path| config_rank(path).is_some())
+            .cloned()
+            .collect();
+        for path in others {
+            let bytes = self.read(&path).await?;
+            let (text, _) = encoding::decode_text(&bytes);
+            let format = ConfigFormat::from_path(&path).expect("Config files have a known format");
+            if format
+                .parse::<Shape>(&text)
+                .is_ok_and(|shape| shape.task.is_some())
+            {
+                self.progress.println(format_args!(
+                    "Dropped {}, replaced by {}",
+                    path.display(),
+                    target_path.display()
+                ));
+                self.entries.remove(&path);
             }
         }
+        Ok(())
+    }
+
+    fn generate(
+        &mut self,
+        format: ConfigFormat,
+        overrides: &ConfigOverrides,
+    ) -> anyhow::Result<&mut Self> {
+        let files = self
+            .entries
+            .iter()
+            .filter(|(_, content)| !matches!(content, Content::Dir))
+            .map(|(path, _)| path.as_path());
+        let config = GeneratedConfig::from_paths(files, overrides.clone()).ok_or(Error::NoCases)?;
+        self.progress.println(format_args!(
+            "Generated {} with {} cases",
+            format.file_name(),
+            config.cases()
+        ));
+
+        let config: Box<dyn Config> = Box::new(config);
+        let target = CasesConfig::try_from(config)?;
+        let target_path = PathBuf::from(format.file_name());
+        self.entries.insert(
+            target_path.clone(),
+            Content::Generated(format.serialize(&target)?.into_bytes()),
+        );
+        self.configs.insert(target_path);
         Ok(self)
     }
 
-    pub async fn convert(&self) -> anyhow::Result<&Self> {
-        for config_path in self.config_paths.iter() {
-            let reader = File::open(&config_path).await?;
+    /// Reads the content of the package entry at `path`.
+    async fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        match &self.entries[path] {
+            Content::Dir => Err(io::Error::from(io::ErrorKind::IsADirectory))?,
+            Content::File(file_path) => Ok(fs::read(file_path).await?),
+            &Content::Zipped(index) => {
+                let input = Arc::clone(&self.input);
+                task::spawn_blocking(move || {
+                    let Input::Zip {
+                        path: archive_path,
+                        limits,
+                    } = &*input
+                    else {
+                        unreachable!("Zipped entries come from ZIP inputs");
+                    };
+                    archive::read_zip(archive_path, limits, |zip| {
+                        let mut bytes = Vec::new();
+                        zip.file(index)?.1.read_to_end(&mut bytes)?;
+                        Ok(bytes)
+                    })
+                })
+                .await?
+            }
+            Content::Generated(bytes) => Ok(bytes.clone()),
+        }
+    }
 
-            // TODO: Erase the concrete type here.
-            let raw: Config1 = serde_yaml_ng::from_reader(reader.into_std().await)?;
-            let config: Box<dyn Config> = Box::new(raw);
-            let target = CasesConfig::try_from(config)?;
-            let parent_dir = config_path.parent().expect("No parent directory");
-            let toml_path = parent_dir.join("config.toml");
-            let mut toml_file = File::create(&toml_path).await?;
+    /// Returns the directories of the problems in the input, i.e. the
+    /// directories with a config file, or the root if there is none.
+    ///
+    /// Problems can't be nested inside each other.
+    pub fn problems(&self) -> crate::error::Result<Vec<PathBuf>> {
+        let mut dirs: Vec<_> = self
+            .config_paths()
+            .into_iter()
+            .map(|path| path.parent().map(Path::to_path_buf).unwrap_or_default())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        if dirs.is_empty() {
+            dirs.push(PathBuf::new());
+        }
 
-            toml_file
-                .write_all(toml::to_string(&target)?.as_bytes())
-                .await?;
+        for outer in &dirs {
+            if let Some(inner) = dirs
+                .iter()
+                .find(|inner| *inner != outer && inner.starts_with(outer))
+            {
+                let outer = if outer.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    outer
+                };
+                Err(Error::NestedProblem {
+                    outer: outer.to_path_buf(),
+                    inner: inner.clone(),
+                })?;
+            }
         }
 
-        Ok(self)
+        let outside = self
+            .entries
+            .iter()
+            .filter(|(path, content)| {
+                !matches!(content, Content::Dir) && !dirs.iter().any(|dir| path.starts_with(dir))
+            })
+            .count();
+        if outside > 0 {
+            self.progress.println(format_args!(
+                "Skipped {outside} files outside of problem directories"
+            ));
+        }
+
+        Ok(dirs)
     }
 
-    pub async fn tar(&self, output_path: impl AsRef<Path>) -> anyhow::Result<()> {
-        async fn inner(temp_dir: &TempDir, output_path: &Path) -> anyhow::Result<()> {
-            let tar_file = output_path.join("config.tar.zst");
+    /// Writes the package of the problem in `problem`, see
+    /// [`Converter::problems`], to `output_path` and returns its path.
+    ///
+    /// Packaging and compression run on a blocking thread.
+    pub async fn package(
+        &self,
+        problem: impl AsRef<Path>,
+        output_path: impl AsRef<Path>,
+        name: &str,
+        options: &PackageOptions,
+    ) -> anyhow::Result<PathBuf> {
+        async fn inner(
+            converter: &Converter,
+            problem: &Path,
+            output_path: &Path,
+            name: &str,
+            options: &PackageOptions,
+        ) -> anyhow::Result<PathBuf> {
             fs::create_dir_all(&output_path).await?;
-            let file = File::create(&tar_file).await?;
-            let encoder = zstd::Encoder::new(file.into_std().await, 1)?.auto_finish();
-            let mut tar_builder = tar::Builder::new(encoder);
-            tar_builder.append_dir_all("config", temp_dir)?;
-            tar_builder.finish()?;
+            let package_path = output_path.join(options.format.file_name(name));
 
-            Ok(())
+            let input = Arc::clone(&converter.input);
+            let entries = converter
+                .entries
+                .iter()
+                .filter_map(|(path, content)| {
+                    let path = path.strip_prefix(problem).ok()?;
+                    (!path.as_os_str().is_empty()).then(|| (path.to_path_buf(), content.clone()))
+                })
+                .collect();
+            let configs = converter
+                .configs
+                .iter()
+                .filter_map(|path| Some(path.strip_prefix(problem).ok()?.to_path_buf()))
+                .collect();
+            let progress = converter.progress.clone();
+            let options = options.clone();
+            task::spawn_blocking(move || {
+                let package = options.create(&package_path, &progress)?;
+                write_package(package, &options.root, &input, &entries, &configs)?;
+                Ok(package_path)
+            })
+            .await?
         }
-        inner(&self.temp_dir, output_path.as_ref()).await
+        inner(self, problem.as_ref(), output_path.as_ref(), name, options).await
+    }
+
+    /// Returns the problem id from the config of the problem in `problem`.
+    pub fn problem_id(&self, problem: &Path) -> Option<&str> {
+        self.problem_ids.get(problem).map(String::as_str)
+    }
+}
+
+fn write_package(
+    mut package: Box<dyn Package>,
+    root: &str,
+    input: &Input,
+    entries: &BTreeMap<PathBuf, Content>,
+    configs: &BTreeSet<PathBuf>,
+) -> anyhow::Result<()> {
+    match input {
+        Input::Extracted { .. } => append_entries(package.as_mut(), root, entries, configs, None)?,
+        Input::Zip { path, limits } => archive::read_zip(path, limits, |zip| {
+            append_entries(package.as_mut(), root, entries, configs, Some(zip))
+        })?,
     }
+    package.finish()
 }
 
-async fn find_zip_file(path: impl AsRef<Path>) -> io::Result<Option<PathBuf>> {
-    async fn inner(path: &Path) -> io::Result<Option<PathBuf>> {
-        while let Some(entry) = fs::read_dir(path).await?.next_entry().await? {
-            if let Some(ext) = entry.path().extension() {
-                if ext == "zip" {
-                    return Ok(Some(path.to_path_buf()));
+/// Adds the entries to `package`, followed by their manifest listing the
+/// hashes of `configs`.
+fn append_entries(
+    package: &mut dyn Package,
+    root: &str,
+    entries: &BTreeMap<PathBuf, Content>,
+    configs: &BTreeSet<PathBuf>,
+    mut zip: Option<&mut ZipEntries>,
+) -> anyhow::Result<()> {
+    let root = Path::new(root);
+    package.add_dir(root)?;
+
+    let mut manifest = Manifest::default();
+    for (path, content) in entries {
+        // Replaced by the manifest of this package.
+        if path == Path::new(MANIFEST_FILE) {
+            continue;
+        }
+
+        let name = root.join(path);
+        let mut add_file = |size, reader: &mut dyn Read| -> anyhow::Result<()> {
+            let mut reader = HashReader::new(Interruptible(reader));
+            package.add_file(&name, size, &mut reader)?;
+            let (size, sha256) = reader.finish();
+
+            let is_config = configs.contains(path);
+            let path = package::entry_name(path)?;
+            if is_config {
+                manifest.configs.insert(path.clone(), sha256.clone());
+            }
+            manifest.files.push(ManifestFile { path, size, sha256 });
+            Ok(())
+        };
+
+        match content {
+            Content::Dir => package.add_dir(&name)?,
+            Content::File(file_path) => {
+                // Symlinks were resolved when indexing, see `index_dir`.
+                if std::fs::symlink_metadata(file_path)?.is_symlink() {
+                    Err(Error::EscapingSymlink {
+                        path: path.clone(),
+                        target: std::fs::read_link(file_path)?,
+                    })?;
                 }
+                let mut file = std::fs::File::open(file_path)?;
+                let size = file.metadata()?.len();
+                add_file(size, &mut file)?;
             }
+            Content::Zipped(index) => {
+                let zip = zip
+                    .as_deref_mut()
+                    .expect("Zipped entries come from ZIP inputs");
+                let (size, mut reader) = zip.file(*index)?;
+                add_file(size, &mut reader)?;
+            }
+            Content::Generated(bytes) => add_file(bytes.len() as u64, &mut &bytes[..])?,
         }
-        Ok(None)
     }
 
-    inner(path.as_ref()).await
+    let manifest = toml::to_string(&manifest)?.into_bytes();
+    package.add_file(
+        &root.join(MANIFEST_FILE),
+        manifest.len() as u64,
+        &mut &manifest[..],
+    )?;
+
+    Ok(())
 }
 
-async fn extract_config_file(
-    zip_path: impl AsRef<Path>,
-    temp_dir: impl AsRef<Path>,
-) -> anyhow::Result<Vec<PathBuf>> {
-    async fn inner(zip_path: &Path, temp_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
-        let file = fs::File::open(zip_path).await?;
-        ZipArchive::new(file.into_std().await)?.extract(temp_dir)?;
+/// Fields of a config document that tell a [`CasesConfig`] apart from raw
+/// configs, which have no `task`.
+#[derive(Deserialize)]
+struct Shape {
+    task: Option<IgnoredAny>,
+}
+
+/// Maps a test data path to its SAST-OJ name, keeping the trailing digits of
+/// the stem, e.g. `data/sample12.ans` to `data/12.out`.
+fn renamed(path: &Path) -> Option<PathBuf> {
+    fn map_ext(orig: &str) -> Cow<'_, str> {
+        match orig {
+            "in" => Cow::Borrowed(orig),
+            _ => Cow::Owned(String::from("out")),
+        }
+    }
+
+    let (stem, ext @ (b"in" | b"out" | b"ans")) = (
+        path.file_stem()?.as_encoded_bytes(),
+        path.extension()?.as_encoded_bytes(),
+    ) else {
+        return None;
+    };
+
+    let digits = if let Some(pos) = stem.iter().rposition(|b| !b.is_ascii_digit()) {
+        &stem[pos + 1..]
+    } else {
+        stem
+    };
+
+    // SAFETY: `digits` is composed of ASCII digits only
+    let new_stem = unsafe { str::from_utf8_unchecked(digits) };
+
+    // SAFETY: `ext` is one of `"in"` / `"out"` / `"ans"`, which are all ASCII-only
+    let new_ext = map_ext(unsafe { str::from_utf8_unchecked(ext) });
+    Some(path.with_file_name(format!("{new_stem}.{new_ext}")))
+}
+
+/// Lists the entries below `dir`, keyed by their path relative to it.
+///
+/// Symlinks are resolved, see [`resolve_symlink`].
+async fn index_dir(dir: &Path) -> crate::error::Result<BTreeMap<PathBuf, Content>> {
+    let mut index = BTreeMap::new();
+
+    let dir = fs::canonicalize(dir).await?;
+    let mut entries = WalkDir::new(&dir);
+    while let Some(entry) = entries.try_next().await? {
+        let path = entry.path();
+        let relative = path
+            .strip_prefix(&dir)
+            .expect("Entry outside of root")
+            .to_path_buf();
+        let file_type = entry.file_type().await?;
+        let content = if file_type.is_dir() {
+            Content::Dir
+        } else if file_type.is_symlink() {
+            Content::File(resolve_symlink(&dir, &path).await?)
+        } else {
+            Content::File(path)
+        };
+        index.insert(relative, content);
+    }
+
+    Ok(index)
+}
 
-        let mut yaml_files = Vec::new();
+/// Resolves the symlink at `path` below the canonical `root`.
+///
+/// Links are checked when archives are extracted, but flattening wrapper
+/// directories moves them, so links leading outside of `root` are rejected
+/// again here.
+async fn resolve_symlink(root: &Path, path: &Path) -> crate::error::Result<PathBuf> {
+    let resolved = fs::canonicalize(path).await?;
+    if !resolved.starts_with(root) {
+        return Err(Error::EscapingSymlink {
+            path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            target: resolved,
+        });
+    }
+    Ok(resolved)
+}
 
-        let mut entries = WalkDir::new(temp_dir);
+/// Files of a Git checkout that don't belong in packages.
+const GIT_FILES: [&str; 3] = [".git", ".gitignore", ".gitattributes"];
+
+/// Copies the problem directory `from` to `to`, following symlinks that stay
+/// inside `from`, see [`resolve_symlink`].
+///
+/// Symlinked directories are copied like the directories they point to.
+async fn copy_dir(
+    from: &Path,
+    to: &Path,
+    options: &ExtractOptions,
+    report: &mut ExtractReport,
+) -> crate::error::Result<()> {
+    let root = fs::canonicalize(from).await?;
+    // Directories left to copy, with their destination and the directories
+    // they were reached through, to detect cycles.
+    let mut pending = vec![(root.clone(), to.to_path_buf(), vec![root.clone()])];
+
+    while let Some((dir, dest, chain)) = pending.pop() {
+        let mut entries = WalkDir::new(&dir).filter(|entry| async move {
+            if GIT_FILES.iter().any(|name| entry.file_name() == *name) {
+                Filtering::IgnoreDir
+            } else {
+                Filtering::Continue
+            }
+        });
         while let Some(entry) = entries.try_next().await? {
             let path = entry.path();
-            if path.file_name() == Some(OsStr::new("config.yaml"))
-                || path.file_name() == Some(OsStr::new("config.yml"))
-            {
-                yaml_files.push(path);
+            let target = dest.join(path.strip_prefix(&dir).expect("Entry outside of root"));
+            let relative = target.strip_prefix(to).expect("Target outside of root");
+
+            if options.junk.is_junk(relative) {
+                report.dropped.push(target);
+                continue;
             }
-        }
 
-        eprintln!("Found {} YAML files", yaml_files.len());
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                fs::create_dir_all(&target).await?;
+                continue;
+            }
 
-        Ok(yaml_files)
+            let source = if file_type.is_symlink() {
+                resolve_symlink(&root, &path).await?
+            } else {
+                path.clone()
+            };
+            if file_type.is_symlink() && fs::metadata(&source).await?.is_dir() {
+                if path.starts_with(&source) || chain.iter().any(|dir| dir.starts_with(&source)) {
+                    return Err(Error::SymlinkCycle(relative.to_path_buf()));
+                }
+                fs::create_dir_all(&target).await?;
+                let chain = chain.iter().cloned().chain([source.clone()]).collect();
+                pending.push((source, target, chain));
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            report.progress.inc(fs::copy(&source, &target).await?);
+        }
+    }
+    Ok(())
+}
+
+fn print_report(report: &ExtractReport, root: &Path) {
+    let progress = &report.progress;
+    for encoding in &report.decoded {
+        progress.println(format_args!(
+            "Decoded non-UTF-8 file names as {}",
+            encoding.name()
+        ));
+    }
+    if !report.dropped.is_empty() {
+        progress.println(format_args!(
+            "Dropped {} junk entries:",
+            report.dropped.len()
+        ));
+        for path in &report.dropped {
+            progress.println(format_args!(
+                "  {}",
+                path.strip_prefix(root).unwrap_or(path).display()
+            ));
+        }
     }
-    inner(zip_path.as_ref(), temp_dir.as_ref()).await
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::BTreeMap,
+        io::Write as _,
+        path::{Path, PathBuf},
+    };
+
+    use tempfile::{NamedTempFile, tempdir};
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::{Content, Converter, Input, renamed};
+    use crate::{
+        archive::ExtractOptions,
+        discovery::is_problem_dir,
+        error::Error,
+        model::{cases_config::CasesConfig, format::ConfigFormat, raw::generated::ConfigOverrides},
+        package::{OutputFormat, PackageOptions},
+        progress::Progress,
+    };
+
+    fn converter(paths: &[&str]) -> Converter {
+        let files: Vec<_> = paths.iter().map(|path| (*path, "")).collect();
+        with_files(&files)
+    }
+
+    fn with_files(files: &[(&str, impl AsRef<[u8]>)]) -> Converter {
+        let entries = files
+            .iter()
+            .map(|(path, bytes)| {
+                let content = Content::Generated(bytes.as_ref().to_vec());
+                (PathBuf::from(path), content)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let input = Input::Extracted {
+            _dir: tempdir().unwrap(),
+        };
+        Converter::new(input, entries, Progress::default())
+    }
+
+    #[test]
+    fn problems() {
+        let problems = converter(&[
+            "A/config.yaml",
+            "A/1.in",
+            "B/config.yml",
+            "C/config.json",
+            "C/config.toml",
+            "README.md",
+        ])
+        .problems()
+        .unwrap();
+        assert_eq!(problems, [Path::new("A"), Path::new("B"), Path::new("C")]);
+
+        let problems = converter(&["config.yaml", "1.in"]).problems().unwrap();
+        assert_eq!(problems, [Path::new("")]);
+        let problems = converter(&["1.in"]).problems().unwrap();
+        assert_eq!(problems, [Path::new("")]);
+
+        let nested = converter(&["config.yaml", "sub/config.yaml"]).problems();
+        assert!(matches!(
+            nested,
+            Err(Error::NestedProblem { outer, inner }) if outer == Path::new(".") && inner == Path::new("sub")
+        ));
+    }
+
+    #[tokio::test]
+    async fn convert_sources() -> anyhow::Result<()> {
+        let cases_config = r#"score = 100
+judge = { judgeType = "classic" }
+resourceLimits = { time = 1000, memory = 256 }
+task = { taskType = "simple", cases = [{ input = "1.in", answer = "1.out" }] }
+"#;
+        let mut converter = with_files(&[
+            ("A/config.json", r#"{ "id": 7, "time": "2s" }"#),
+            ("B/config.toml", r#"time = "3s""#),
+            ("C/config.toml", cases_config),
+            ("D/config.yaml", "time: 4s"),
+            ("D/config.toml", cases_config),
+        ]);
+        converter
+            .convert(ConfigFormat::Toml, &ConfigOverrides::default())
+            .await?;
+
+        for (problem, time) in [("A", 2000), ("B", 3000), ("D", 4000)] {
+            let config = converter
+                .read(&Path::new(problem).join("config.toml"))
+                .await?;
+            let config: CasesConfig = toml::from_slice(&config)?;
+            assert_eq!(config.resource_limits.time, time, "{problem}");
+        }
+        assert_eq!(converter.problem_id(Path::new("A")), Some("7"));
+        let kept = converter.read(Path::new("C/config.toml")).await?;
+        assert_eq!(kept, cases_config.as_bytes());
+
+        // Kept configs are transcoded too.
+        let utf16: Vec<_> = [0xff, 0xfe]
+            .into_iter()
+            .chain(cases_config.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let mut converter = with_files(&[("config.toml", utf16)]);
+        converter
+            .convert(ConfigFormat::Toml, &ConfigOverrides::default())
+            .await?;
+        let kept = converter.read(Path::new("config.toml")).await?;
+        assert_eq!(kept, cases_config.as_bytes());
+
+        let mut converter =
+            with_files(&[("config.yaml", "time: 2s"), ("config.toml", cases_config)]);
+        converter
+            .convert(ConfigFormat::Json, &ConfigOverrides::default())
+            .await?;
+        let config = converter.read(Path::new("config.json")).await?;
+        let config: CasesConfig = serde_json::from_slice(&config)?;
+        assert_eq!(config.resource_limits.time, 2000);
+        assert_eq!(converter.configs, [PathBuf::from("config.json")].into());
+        let paths: Vec<_> = converter.entries.keys().collect();
+        assert_eq!(paths, ["config.json", "config.yaml"].map(Path::new));
+
+        // A kept config is replaced when written in another format.
+        let mut converter = with_files(&[("config.toml", cases_config)]);
+        converter
+            .convert(ConfigFormat::Yaml, &ConfigOverrides::default())
+            .await?;
+        let paths: Vec<_> = converter.entries.keys().collect();
+        assert_eq!(paths, [Path::new("config.yaml")]);
+        let config = converter.read(Path::new("config.yaml")).await?;
+        let config: CasesConfig = serde_yaml_ng::from_slice(&config)?;
+        assert_eq!(config.resource_limits.time, 1000);
+
+        let mut invalid = with_files(&[("config.toml", "[task]\ntaskType = \"simple\"")]);
+        assert!(
+            invalid
+                .convert(ConfigFormat::Toml, &ConfigOverrides::default())
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn problem_dir() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let problem = dir.path().join("A");
+        std::fs::create_dir_all(problem.join(".git/objects"))?;
+        std::fs::create_dir_all(problem.join("data"))?;
+        std::fs::write(problem.join(".git/HEAD"), "ref: refs/heads/main")?;
+        std::fs::write(problem.join("config.yaml"), "time: 1s")?;
+        std::fs::write(problem.join("data/1.in"), "1 2")?;
+        std::fs::write(problem.join(".DS_Store"), "")?;
+        std::fs::write(problem.join(".gitignore"), "*.o")?;
+        std::fs::write(problem.join("data/.gitattributes"), "* text")?;
+
+        assert!(is_problem_dir(&problem).await?);
+        assert!(!is_problem_dir(dir.path()).await?);
+        assert!(!is_problem_dir(&problem.join("config.yaml")).await?);
+
+        let converter =
+            Converter::with_problem_dir(&problem, &ExtractOptions::default(), Progress::default())
+                .await?;
+        let paths: Vec<_> = converter.entries.keys().collect();
+        assert_eq!(paths, ["config.yaml", "data", "data/1.in"].map(Path::new));
+        assert_eq!(converter.read(Path::new("data/1.in")).await?, b"1 2");
+
+        // The source tree is left as it was.
+        assert!(problem.join(".git/HEAD").exists());
+        assert!(problem.join(".DS_Store").exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn escaping_symlinks() -> anyhow::Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir()?;
+        let outside = NamedTempFile::new()?;
+        let outside_name = outside.path().file_name().unwrap();
+
+        // Stays inside the extraction directory until `w/` is flattened.
+        let archive_path = dir.path().join("a.tar");
+        let mut builder = tar::Builder::new(std::fs::File::create(&archive_path)?);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, "w/2.in", Path::new("..").join(outside_name))?;
+        builder.finish()?;
+
+        let options = ExtractOptions::default();
+        let Err(err) = Converter::with_archive(&archive_path, &options, Progress::default()).await
+        else {
+            panic!("Escaping symlink was followed");
+        };
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::EscapingSymlink { .. })
+        ));
+
+        let problem = dir.path().join("problem");
+        std::fs::create_dir(&problem)?;
+        std::fs::write(problem.join("config.yaml"), "")?;
+        std::fs::write(problem.join("1.in"), "1")?;
+        symlink("1.in", problem.join("2.in"))?;
+        std::fs::create_dir(problem.join("data"))?;
+        std::fs::write(problem.join("data/3.in"), "3")?;
+        symlink("data", problem.join("samples"))?;
+        let converter =
+            Converter::with_problem_dir(&problem, &options, Progress::default()).await?;
+        assert_eq!(converter.read(Path::new("2.in")).await?, b"1");
+        assert_eq!(converter.read(Path::new("samples/3.in")).await?, b"3");
+
+        symlink("..", problem.join("data/loop"))?;
+        let Err(err) = Converter::with_problem_dir(&problem, &options, Progress::default()).await
+        else {
+            panic!("Symlink cycle was followed");
+        };
+        assert!(matches!(err.downcast_ref(), Some(Error::SymlinkCycle(_))));
+        std::fs::remove_file(problem.join("data/loop"))?;
+
+        symlink(outside.path(), problem.join("3.in"))?;
+        let Err(err) = Converter::with_problem_dir(&problem, &options, Progress::default()).await
+        else {
+            panic!("Escaping symlink was followed");
+        };
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::EscapingSymlink { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_matches_extract() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let archive_path = dir.path().join("a.zip");
+        let mut writer = ZipWriter::new(std::fs::File::create(&archive_path)?);
+        for (name, content) in [
+            ("w/config.yaml", "time: 1s"),
+            ("w/data/1.in", "1 2"),
+            ("w/data/sample1.ans", "3"),
+            ("__MACOSX/w/._config.yaml", ""),
+        ] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(content.as_bytes())?;
+        }
+        writer.finish()?;
+
+        let mut packages = Vec::new();
+        for stream in [false, true] {
+            let options = ExtractOptions {
+                stream,
+                ..ExtractOptions::default()
+            };
+            let mut converter =
+                Converter::with_input_path(&archive_path, &options, Progress::default()).await?;
+            converter.rename();
+            converter
+                .convert(ConfigFormat::Toml, &ConfigOverrides::default())
+                .await?;
+            let package = converter
+                .package(
+                    "",
+                    dir.path().join(stream.to_string()),
+                    "config",
+                    &PackageOptions {
+                        format: OutputFormat::Tar,
+                        ..PackageOptions::default()
+                    },
+                )
+                .await?;
+            packages.push(std::fs::read(package)?);
+        }
+        assert!(packages[0] == packages[1], "Packages differ");
+        Ok(())
+    }
+
+    #[test]
+    fn rename_test_data() {
+        let cases = [
+            ("data/sample12.ans", Some("data/12.out")),
+            ("3.in", Some("3.in")),
+            ("config.yaml", None),
+            ("1.txt", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                renamed(Path::new(path)),
+                expected.map(PathBuf::from),
+                "{path}"
+            );
+        }
+    }
+
     #[test]
     fn test() {
         let os = {
@@ -167,16 +1112,14 @@ mod tests {
             {
                 use std::{ffi::OsStr, os::unix::ffi::OsStrExt as _};
                 static SOURCE: &[u8] = &[0x66, 0x6f, 0x80, 0x6f, b'1', b'2'];
-                let os_str = OsStr::from_bytes(SOURCE);
-                os_str
+                OsStr::from_bytes(SOURCE)
             }
             #[cfg(windows)]
             {
                 use std::{ffi::OsString, os::windows::ffi::OsStringExt as _};
 
                 static SOURCE: &[u16] = &[0x0066, 0xD800, b'1' as _, b'2' as _];
-                let os_string = OsString::from_wide(SOURCE);
-                os_string
+                OsString::from_wide(SOURCE)
             }
         };
         let bytes = os.as_encoded_bytes();
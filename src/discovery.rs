@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use async_walkdir::{Filtering, WalkDir};
+use clap::ValueEnum;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use tokio::fs;
+use tokio_stream::StreamExt;
+
+use crate::{archive::ArchiveFormat, error::Error};
+
+/// An input to convert, i.e. an archive or a problem directory.
+#[derive(Debug)]
+pub struct Source {
+    pub path: PathBuf,
+    /// Path of the source relative to the discovery root, empty if the input
+    /// was given directly.
+    pub relative: PathBuf,
+}
+
+/// How output directories are laid out for discovered archives.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Layout {
+    /// `<OUTPUT>/<relative directory>/<archive stem>/`, mirroring the input tree
+    #[default]
+    Mirror,
+    /// `<OUTPUT>/<archive stem>/`
+    Flat,
+}
+
+impl Layout {
+    pub fn output_dir(self, output_root: &Path, source: &Source) -> PathBuf {
+        let Some(stem) = source.relative.file_name().map(Path::new) else {
+            return output_root.to_path_buf();
+        };
+        let stem = ArchiveFormat::strip_extension(stem);
+
+        match (self, source.relative.parent()) {
+            (Self::Mirror, Some(parent)) => output_root.join(parent).join(stem),
+            _ => output_root.join(stem),
+        }
+    }
+}
+
+/// Recursive archive discovery, filtered by glob patterns.
+///
+/// Patterns without a `/` match file or directory names at any depth, other
+/// patterns match paths relative to the discovery root.
+#[derive(Debug)]
+pub struct Discovery {
+    include: GlobSet,
+    exclude: GlobSet,
+    skip_dir: Option<PathBuf>,
+}
+
+impl Discovery {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        skip_dir: Option<PathBuf>,
+    ) -> Result<Self, globset::Error> {
+        fn build(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in patterns {
+                let pattern = if pattern.contains('/') {
+                    pattern.trim_start_matches('/').to_owned()
+                } else {
+                    format!("**/{pattern}")
+                };
+                builder.add(GlobBuilder::new(&pattern).literal_separator(true).build()?);
+            }
+            builder.build()
+        }
+
+        Ok(Self {
+            include: build(include)?,
+            exclude: build(exclude)?,
+            skip_dir: skip_dir.and_then(|dir| dir.canonicalize().ok()),
+        })
+    }
+
+    /// Finds the sources under `input`.
+    ///
+    /// A file or a problem directory is returned as is, otherwise `input` is
+    /// searched recursively for archives.
+    pub async fn discover(&self, input: &Path) -> crate::error::Result<Vec<Source>> {
+        if fs::metadata(input).await?.is_file() || is_problem_dir(input).await? {
+            return Ok(vec![Source {
+                path: input.to_path_buf(),
+                relative: PathBuf::new(),
+            }]);
+        }
+
+        let root = fs::canonicalize(input).await?;
+        let exclude = self.exclude.clone();
+        let skip_dir = self.skip_dir.clone();
+        let filter_root = root.clone();
+        let mut entries = WalkDir::new(&root).filter(move |entry| {
+            let path = entry.path();
+            let excluded = path
+                .strip_prefix(&filter_root)
+                .is_ok_and(|relative| exclude.is_match(relative))
+                || skip_dir.as_ref() == Some(&path);
+
+            async move {
+                if excluded {
+                    Filtering::IgnoreDir
+                } else {
+                    Filtering::Continue
+                }
+            }
+        });
+
+        let mut sources = Vec::new();
+        while let Some(entry) = entries.try_next().await? {
+            let path = entry.path();
+            let relative = path.strip_prefix(&root).expect("Entry outside of root");
+
+            if ArchiveFormat::from_extension(&path).is_some()
+                && (self.include.is_empty() || self.include.is_match(relative))
+                && entry.file_type().await?.is_file()
+            {
+                sources.push(Source {
+                    relative: relative.to_path_buf(),
+                    path,
+                });
+            }
+        }
+
+        if sources.is_empty() {
+            return Err(Error::NoInput(input.to_path_buf()));
+        }
+        sources.sort_by(|a, b| a.relative.cmp(&b.relative));
+
+        Ok(sources)
+    }
+}
+
+/// Checks whether `path` is a problem directory, i.e. a directory with a config
+/// file at its top level.
+pub async fn is_problem_dir(path: &Path) -> std::io::Result<bool> {
+    if !fs::metadata(path).await?.is_dir() {
+        return Ok(false);
+    }
+
+    let mut entries = fs::read_dir(path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if is_config_file(&entry.path()) && entry.file_type().await?.is_file() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Names of config files, in order of preference if a directory has several.
+const CONFIG_FILES: [&str; 4] = ["config.yaml", "config.yml", "config.json", "config.toml"];
+
+pub fn is_config_file(path: &Path) -> bool {
+    config_rank(path).is_some()
+}
+
+/// Returns the preference of the config file at `path`, lower is preferred,
+/// see [`CONFIG_FILES`].
+pub fn config_rank(path: &Path) -> Option<usize> {
+    let name = path.file_name()?.to_str()?;
+    CONFIG_FILES.iter().position(|file| *file == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{Discovery, Layout, Source};
+
+    #[test]
+    fn patterns() {
+        let discovery = Discovery::new(
+            &["day1/**".into()],
+            &["*.bak.zip".into(), "drafts".into()],
+            None,
+        )
+        .unwrap();
+
+        assert!(discovery.include.is_match("day1/A/A.zip"));
+        assert!(!discovery.include.is_match("day2/A/A.zip"));
+        assert!(discovery.exclude.is_match("day1/A/A.bak.zip"));
+        assert!(discovery.exclude.is_match("day2/drafts"));
+        assert!(!discovery.exclude.is_match("day2/drafts.zip"));
+    }
+
+    #[test]
+    fn layout() {
+        let source = Source {
+            path: PathBuf::from("contest/day1/A/A.tar.gz"),
+            relative: PathBuf::from("day1/A/A.tar.gz"),
+        };
+        let out = Path::new("out");
+
+        assert_eq!(
+            Layout::Mirror.output_dir(out, &source),
+            Path::new("out/day1/A/A")
+        );
+        assert_eq!(Layout::Flat.output_dir(out, &source), Path::new("out/A"));
+
+        let direct = Source {
+            path: PathBuf::from("A.zip"),
+            relative: PathBuf::new(),
+        };
+        assert_eq!(Layout::Mirror.output_dir(out, &direct), out);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{borrow::Cow, str};
+
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_8};
+
+/// Guesses the legacy encoding of `samples`, e.g. GBK or Shift-JIS.
+pub fn guess<'a>(samples: impl IntoIterator<Item = &'a [u8]>) -> &'static Encoding {
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    for sample in samples {
+        detector.feed(sample, false);
+        detector.feed(b"\n", false);
+    }
+    detector.feed(b"", true);
+    detector.guess(None, Utf8Detection::Deny)
+}
+
+/// Decodes a text file into UTF-8.
+///
+/// A byte order mark takes precedence, then UTF-8 is assumed if the content is
+/// valid, otherwise the encoding is guessed. Returns the text along with the
+/// encoding it was decoded from.
+pub fn decode_text(bytes: &[u8]) -> (Cow<'_, str>, &'static Encoding) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return (text, encoding);
+    }
+
+    match str::from_utf8(bytes) {
+        Ok(text) => (Cow::Borrowed(text), UTF_8),
+        Err(_) => {
+            let encoding = guess([bytes]);
+            (encoding.decode_without_bom_handling(bytes).0, encoding)
+        }
+    }
+}
+
+/// Parses a WHATWG encoding label such as `gbk` or `shift_jis`.
+pub fn parse_label(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding: {label}"))
+}
+
+/// Decodes names that may be stored in a legacy encoding, as ZIP archives
+/// created on non-UTF-8 systems do.
+///
+/// Valid UTF-8 names are kept as is. All other names are decoded with the same
+/// encoding, either the forced one or one guessed from all of them at once,
+/// since single names are often too short to guess from.
+#[derive(Debug)]
+pub struct NameDecoder {
+    legacy: Option<&'static Encoding>,
+}
+
+impl NameDecoder {
+    pub fn new<'a>(
+        names: impl IntoIterator<Item = &'a [u8]>,
+        forced: Option<&'static Encoding>,
+    ) -> Self {
+        let mut legacy_names = names
+            .into_iter()
+            .filter(|name| str::from_utf8(name).is_err())
+            .peekable();
+
+        let legacy = match (legacy_names.peek(), forced) {
+            (None, _) => None,
+            (Some(_), Some(forced)) => Some(forced),
+            (Some(_), None) => Some(guess(legacy_names)),
+        };
+
+        Self { legacy }
+    }
+
+    /// The encoding used for names that are not valid UTF-8, if there are any.
+    pub fn legacy_encoding(&self) -> Option<&'static Encoding> {
+        self.legacy
+    }
+
+    pub fn decode<'a>(&self, name: &'a [u8]) -> Cow<'a, str> {
+        match (str::from_utf8(name), self.legacy) {
+            (Ok(name), _) => Cow::Borrowed(name),
+            (Err(_), Some(legacy)) => legacy.decode_without_bom_handling(name).0,
+            (Err(_), None) => String::from_utf8_lossy(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{GBK, SHIFT_JIS, UTF_8, UTF_16LE};
+
+    use super::{NameDecoder, decode_text};
+
+    #[test]
+    fn decode_text_files() {
+        let yaml = "# 题目配置\ntime: 1s\nmemory: 256\n";
+
+        assert_eq!(decode_text(yaml.as_bytes()), (yaml.into(), UTF_8));
+
+        let with_bom = [b"\xEF\xBB\xBF", yaml.as_bytes()].concat();
+        assert_eq!(decode_text(&with_bom), (yaml.into(), UTF_8));
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(yaml.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_text(&utf16), (yaml.into(), UTF_16LE));
+
+        let gbk = GBK.encode(yaml).0;
+        assert_eq!(decode_text(&gbk), (yaml.into(), GBK));
+    }
+
+    #[test]
+    fn decode_gbk_names() {
+        let names = [
+            "样例1.in",
+            "样例1.ans",
+            "数据/1.in",
+            "数据/1.ans",
+            "config.yaml",
+        ];
+        let raw: Vec<_> = names.iter().map(|name| GBK.encode(name).0).collect();
+
+        let decoder = NameDecoder::new(raw.iter().map(|name| &name[..]), None);
+        assert_eq!(decoder.legacy_encoding(), Some(GBK));
+        for (name, raw) in names.iter().zip(&raw) {
+            assert_eq!(decoder.decode(raw), *name);
+        }
+    }
+
+    #[test]
+    fn forced_encoding() {
+        let raw = SHIFT_JIS.encode("入力1.in").0;
+
+        let decoder = NameDecoder::new([&raw[..]], Some(SHIFT_JIS));
+        assert_eq!(decoder.decode(&raw), "入力1.in");
+        assert_eq!(decoder.decode("1.in".as_bytes()), "1.in");
+    }
+
+    #[test]
+    fn utf8_names() {
+        let decoder = NameDecoder::new(["样例1.in".as_bytes()], Some(SHIFT_JIS));
+        assert_eq!(decoder.legacy_encoding(), None);
+        assert_eq!(decoder.decode("样例1.in".as_bytes()), "样例1.in");
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
-    convert::Infallible, io, num::TryFromIntError, result::Result as StdResult, str::Utf8Error,
+    convert::Infallible, io, num::TryFromIntError, path::PathBuf, result::Result as StdResult,
+    str::Utf8Error,
 };
 
 use thiserror::Error;
@@ -24,6 +25,73 @@ pub enum Error {
     InvalidValue(#[from] TryFromIntError),
     #[error("invalid filename")]
     InvalidFilename(#[from] Utf8Error),
+    #[error("unsupported archive format: {}", .0.display())]
+    UnsupportedArchive(PathBuf),
+    #[error("no archives found in {}", .0.display())]
+    NoInput(PathBuf),
+    #[error("absolute path in archive: {}", .0.display())]
+    AbsolutePath(PathBuf),
+    #[error("path escapes the extraction directory: {}", .0.display())]
+    PathTraversal(PathBuf),
+    #[error("symlink {} escapes the extraction directory through {}", .path.display(), .target.display())]
+    EscapingSymlink { path: PathBuf, target: PathBuf },
+    #[error("symlink {} leads to a directory containing it", .0.display())]
+    SymlinkCycle(PathBuf),
+    #[error("hard link to symlink {}", .0.display())]
+    HardLinkedSymlink(PathBuf),
+    #[error("archive has more than {0} entries")]
+    TooManyEntries(usize),
+    #[error("archive expands to more than {0} bytes")]
+    TooLarge(u64),
+    #[error("compression ratio of {} exceeds {limit}", .path.display())]
+    CompressionRatio { path: PathBuf, limit: u64 },
+    #[error("checksum mismatch for {}", .0.display())]
+    Checksum(PathBuf),
+    #[error("symlink {} can't be streamed", .0.display())]
+    StreamedSymlink(PathBuf),
+    #[error("{} and {} would both be written to {}", .first.display(), .second.display(), .output.display())]
+    OutputCollision {
+        first: PathBuf,
+        second: PathBuf,
+        output: PathBuf,
+    },
+    #[error("file name {} is not valid UTF-8", .0.display())]
+    NonUtf8Name(PathBuf),
+    #[error("invalid SOURCE_DATE_EPOCH: {0}")]
+    SourceDateEpoch(String),
+    #[error("invalid name template: {0}, use {{stem}}, {{contest}} and {{id}}")]
+    InvalidTemplate(String),
+    #[error("{{{0}}} is not known for this input")]
+    MissingName(&'static str),
+    #[error("{0:?} is not a valid file name")]
+    InvalidName(String),
+    #[error("directory packages can't be signed, choose an archive format")]
+    UnsignedDirectory,
+    #[error("--level, --workers and --long only apply to tar-zst packages")]
+    UnusedCompression,
+    #[error("the classic judge takes no --judge-program")]
+    UnusedJudgeProgram,
+    #[error("invalid Ed25519 key: {}", .0.display())]
+    InvalidKey(PathBuf),
+    #[error("invalid signature file: {}", .0.display())]
+    InvalidSignature(PathBuf),
+    #[error("signature doesn't match {}", .0.display())]
+    SignatureMismatch(PathBuf),
+    #[error("{} already exists, use --force to replace it", .0.display())]
+    OutputExists(PathBuf),
+    #[error("interrupted")]
+    Interrupted,
+    #[error("problem {} is nested inside problem {}", .inner.display(), .outer.display())]
+    NestedProblem { outer: PathBuf, inner: PathBuf },
+    #[error("no config file or test data (`<N>.in` and `<N>.out`) found")]
+    NoCases,
+    #[error("{name} must be between {min} and {max}, got {value}")]
+    OutOfRange {
+        name: &'static str,
+        value: String,
+        min: String,
+        max: String,
+    },
     // #[error("Invalid score: {0}")]
     // InvalidScore(u32),
 }
@@ -0,0 +1,48 @@
+use std::{
+    io::{self, Read},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tokio::signal;
+
+use crate::error::Error;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Stops conversions on the first Ctrl-C and exits on the second.
+///
+/// Conversions fail at the next chunk of data they copy, so their temporary
+/// files and directories are removed as they unwind.
+pub fn handle_ctrl_c() {
+    tokio::spawn(async {
+        if signal::ctrl_c().await.is_err() {
+            return;
+        }
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        eprintln!("Interrupted, cleaning up. Press Ctrl-C again to exit now");
+
+        if signal::ctrl_c().await.is_ok() {
+            process::exit(130);
+        }
+    });
+}
+
+/// Fails if the conversion was interrupted.
+pub fn check() -> Result<(), Error> {
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        Err(Error::Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+/// A reader that fails once the conversion is interrupted.
+pub struct Interruptible<R>(pub R);
+
+impl<R: Read> Read for Interruptible<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        check().map_err(io::Error::other)?;
+        self.0.read(buf)
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::{Component, Path};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Matches files that operating systems leave behind in archives, such as
+/// macOS resource forks or Windows thumbnail caches.
+///
+/// Patterns are matched against every component of a path, so a match on a
+/// directory drops everything below it.
+#[derive(Debug, Clone)]
+pub struct JunkFilter {
+    patterns: GlobSet,
+}
+
+impl JunkFilter {
+    pub const DEFAULT_PATTERNS: &[&str] =
+        &["__MACOSX", "._*", ".DS_Store", "Thumbs.db", "desktop.ini"];
+
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern)?);
+        }
+
+        Ok(Self {
+            patterns: builder.build()?,
+        })
+    }
+
+    /// A filter that keeps everything.
+    pub fn none() -> Self {
+        Self {
+            patterns: GlobSet::empty(),
+        }
+    }
+
+    pub fn is_junk(&self, path: &Path) -> bool {
+        path.components().any(|component| match component {
+            Component::Normal(name) => self.patterns.is_match(name),
+            _ => false,
+        })
+    }
+}
+
+impl Default for JunkFilter {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_PATTERNS.iter().copied()).expect("Default patterns are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::JunkFilter;
+
+    #[test]
+    fn default_patterns() {
+        let filter = JunkFilter::default();
+
+        for junk in [
+            "__MACOSX",
+            "__MACOSX/._1.in",
+            "data/._1.in",
+            ".DS_Store",
+            "data/.DS_Store",
+            "Thumbs.db",
+        ] {
+            assert!(filter.is_junk(Path::new(junk)), "{junk}");
+        }
+        for kept in ["1.in", "config.yaml", "data/1.ans", "MACOSX/1.in", "a._b"] {
+            assert!(!filter.is_junk(Path::new(kept)), "{kept}");
+        }
+
+        assert!(!JunkFilter::none().is_junk(Path::new("__MACOSX/._1.in")));
+    }
+}
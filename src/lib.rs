@@ -1,27 +1,424 @@
+mod archive;
 mod cli;
+mod compression;
 mod converter;
+mod discovery;
+mod encoding;
 mod error;
+mod interrupt;
+mod junk;
 mod model;
+mod normalize;
+mod package;
+mod progress;
+mod signature;
+mod template;
 
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use archive::{ArchiveFormat, ExtractLimits, ExtractOptions};
 use clap::Parser;
-use cli::Cli;
-use converter::Converter;
+use cli::{Cli, Command, ConvertArgs, VerifyArgs};
+pub use compression::Compression;
+pub use converter::Converter;
+use discovery::{Discovery, Layout, Source};
+use ed25519_dalek::SigningKey;
+use error::Error;
+use junk::JunkFilter;
+use model::{cases_config::CasesConfig, format::ConfigFormat, raw::generated::ConfigOverrides};
+use normalize::NormalizeOptions;
+pub use package::{OutputFormat, PackageOptions};
+use progress::{Bars, Progress};
+use template::{NameTemplate, NameValues};
+use tokio::{
+    fs,
+    sync::Semaphore,
+    task::{self, JoinSet},
+};
 
 pub async fn main_impl() -> anyhow::Result<()> {
-    let Cli {
+    let cli = Cli::parse();
+    match cli.command {
+        None => convert_all(cli.convert).await,
+        Some(Command::Convert(args)) => convert_all(*args).await,
+        Some(Command::Verify(args)) => verify(args).await,
+        Some(Command::Schema) => schema(),
+    }
+}
+
+/// Converts all inputs found by `args`.
+async fn convert_all(args: ConvertArgs) -> anyhow::Result<()> {
+    let ConvertArgs {
         input: input_path,
         output: output_path,
-    } = Cli::parse();
+        format,
+        include,
+        exclude,
+        layout,
+        max_depth,
+        junk,
+        keep_junk,
+        max_size,
+        max_entries,
+        max_ratio,
+        zip_encoding,
+        normalize,
+        trim_trailing,
+        stream,
+        level,
+        workers,
+        long,
+        jobs,
+        time,
+        memory,
+        score,
+        judge,
+        judge_program,
+        sign,
+        name,
+        root,
+        force,
+        config_format,
+    } = args;
+
+    if sign.is_some() && format == OutputFormat::Dir {
+        Err(Error::UnsignedDirectory)?;
+    }
+    if format != OutputFormat::TarZst && (level.is_some() || workers.is_some() || long.is_some()) {
+        Err(Error::UnusedCompression)?;
+    }
+    let signing_key = sign
+        .map(|path| signature::read_signing_key(&path))
+        .transpose()?;
+
+    let junk = if keep_junk {
+        JunkFilter::none()
+    } else {
+        JunkFilter::new(
+            JunkFilter::DEFAULT_PATTERNS
+                .iter()
+                .copied()
+                .chain(junk.iter().map(String::as_str)),
+        )?
+    };
+    let extract_options = ExtractOptions {
+        max_depth,
+        junk,
+        limits: ExtractLimits {
+            max_size: u64::try_from(max_size.bytes())?,
+            max_entries,
+            max_ratio,
+        },
+        zip_encoding,
+        stream,
+    };
+    let options = Arc::new(Options {
+        extract: extract_options,
+        normalize: normalize.then_some(NormalizeOptions { trim_trailing }),
+        package: PackageOptions {
+            format,
+            compression: Compression {
+                level: level.unwrap_or(Compression::default().level),
+                workers: workers.unwrap_or(Compression::default().workers),
+                long_window_log: long,
+            },
+            root,
+            force,
+        },
+        name,
+        config_format,
+        overrides: ConfigOverrides {
+            time,
+            memory,
+            score,
+            judge: judge
+                .map(|judge| judge.with_program(judge_program))
+                .transpose()?,
+        },
+        signing_key,
+    });
+
+    let discovery = Discovery::new(&include, &exclude, Some(output_path.clone()))?;
+    let sources = discovery.discover(&input_path).await?;
 
-    let converter = Converter::with_input_path(&input_path).await?;
+    let contest = fs::canonicalize(&input_path).await?;
+    let mut targets = Vec::with_capacity(sources.len());
+    for source in &sources {
+        targets.push(Target::new(source, &contest, layout, &output_path).await?);
+    }
+
+    // Fail before extracting anything if two inputs would write the same
+    // packages. Existing packages are checked once the problems of an input
+    // are known, see `convert`, as are names with the problem id.
+    if !options.name.uses_id() {
+        let mut outputs = HashMap::new();
+        for (source, target) in sources.iter().zip(&targets) {
+            let output = target.dir.join(
+                options
+                    .package
+                    .format
+                    .file_name(&options.name.render(&target.values())?),
+            );
+            if let Some(first) = outputs.insert(output.clone(), &source.path) {
+                Err(Error::OutputCollision {
+                    first: first.clone(),
+                    second: source.path.clone(),
+                    output,
+                })?;
+            }
+        }
+    }
+
+    interrupt::handle_ctrl_c();
+    let bars = Bars::new();
+
+    if let ([source], [target]) = (&sources[..], &targets[..]) {
+        let progress = bars.add(display_name(source));
+        return convert(source.path.clone(), target.clone(), options, progress).await;
+    }
+
+    let semaphore = Arc::new(Semaphore::new(jobs.get()));
+    let mut tasks = JoinSet::new();
+    for (index, (source, target)) in sources.iter().zip(&targets).enumerate() {
+        let input = source.path.clone();
+        let target = target.clone();
+        let options = Arc::clone(&options);
+        let semaphore = Arc::clone(&semaphore);
+        let bars = bars.clone();
+        let name = display_name(source);
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            let progress = bars.add(name);
+            anyhow::Ok((index, convert(input, target, options, progress).await))
+        });
+    }
+
+    let paths: Vec<_> = sources.iter().map(|source| source.path.as_path()).collect();
+    join_conversions(tasks, &paths).await
+}
 
-    converter
-        .rename()
-        .await?
-        .convert()
-        .await?
-        .tar(&output_path)
-        .await?;
+/// Waits for the conversions of `paths`, each yielding its index in `paths`,
+/// and reports the failed ones in order. Fails if any conversion failed.
+async fn join_conversions(
+    mut tasks: JoinSet<anyhow::Result<(usize, anyhow::Result<()>)>>,
+    paths: &[&Path],
+) -> anyhow::Result<()> {
+    let mut results = Vec::with_capacity(paths.len());
+    while let Some(result) = tasks.join_next().await {
+        results.push(result??);
+    }
+    results.sort_by_key(|(index, _)| *index);
+
+    let failures: Vec<_> = results
+        .into_iter()
+        .filter_map(|(index, result)| Some((paths[index], result.err()?)))
+        .collect();
+    eprintln!(
+        "Converted {} of {} inputs",
+        paths.len() - failures.len(),
+        paths.len()
+    );
+    for (path, error) in &failures {
+        eprintln!("  Failed {}: {error:#}", path.display());
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "failed to convert {} of {} inputs",
+            failures.len(),
+            paths.len()
+        );
+    }
 
     Ok(())
 }
+
+/// Options shared by the conversions of all inputs.
+struct Options {
+    extract: ExtractOptions,
+    normalize: Option<NormalizeOptions>,
+    package: PackageOptions,
+    name: NameTemplate,
+    config_format: ConfigFormat,
+    overrides: ConfigOverrides,
+    signing_key: Option<SigningKey>,
+}
+
+impl Options {
+    /// Fails if the package at `path` or its signature already exists and may
+    /// not be replaced.
+    fn check_outputs(&self, path: &Path) -> crate::error::Result<()> {
+        self.package.check_output(path)?;
+        if self.signing_key.is_some() {
+            self.package
+                .check_output(&signature::signature_path(path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the package of a source is written.
+#[derive(Debug, Clone)]
+struct Target {
+    dir: PathBuf,
+    stem: String,
+    contest: Option<String>,
+}
+
+impl Target {
+    /// Creates the target of `source`, found by searching `contest`.
+    async fn new(
+        source: &Source,
+        contest: &Path,
+        layout: Layout,
+        output_path: &Path,
+    ) -> io::Result<Self> {
+        let (path, contest) = if source.relative.as_os_str().is_empty() {
+            let path = fs::canonicalize(&source.path).await?;
+            let contest = path.parent().map(Path::to_path_buf);
+            (path, contest)
+        } else {
+            (source.relative.clone(), Some(contest.to_path_buf()))
+        };
+        let name = |path: &Path| Some(path.file_name()?.to_string_lossy().into_owned());
+
+        Ok(Self {
+            dir: layout.output_dir(output_path, source),
+            stem: ArchiveFormat::strip_extension(&path)
+                .to_string_lossy()
+                .into_owned(),
+            contest: contest.as_deref().and_then(name),
+        })
+    }
+
+    fn values(&self) -> NameValues<'_> {
+        NameValues {
+            stem: &self.stem,
+            contest: self.contest.as_deref(),
+            id: None,
+        }
+    }
+}
+
+/// Name of a source shown in progress bars.
+fn display_name(source: &Source) -> String {
+    let path = if source.relative.as_os_str().is_empty() {
+        source
+            .path
+            .file_name()
+            .map_or(source.path.as_path(), Path::new)
+    } else {
+        source.relative.as_path()
+    };
+    path.display().to_string()
+}
+
+/// Converts one input into a package at `target`.
+async fn convert(
+    input: PathBuf,
+    target: Target,
+    options: Arc<Options>,
+    progress: Progress,
+) -> anyhow::Result<()> {
+    async fn inner(
+        input: &Path,
+        target: &Target,
+        options: &Options,
+        progress: Progress,
+    ) -> anyhow::Result<()> {
+        interrupt::check()?;
+        let mut converter = Converter::with_input_path(input, &options.extract, progress).await?;
+
+        converter.rename();
+        if let Some(options) = &options.normalize {
+            converter.normalize(options).await?;
+        }
+        converter
+            .convert(options.config_format, &options.overrides)
+            .await?;
+
+        // Problems in subdirectories are written to the same subdirectories.
+        // All of them are checked before the first package is written.
+        let mut packages = Vec::new();
+        for problem in converter.problems()? {
+            let name = options.name.render(&NameValues {
+                id: converter.problem_id(&problem),
+                ..target.values()
+            })?;
+            let dir = target.dir.join(&problem);
+            options.check_outputs(&dir.join(options.package.format.file_name(&name)))?;
+            packages.push((problem, dir, name));
+        }
+
+        for (problem, dir, name) in packages {
+            let package = converter
+                .package(&problem, dir, &name, &options.package)
+                .await?;
+
+            if let Some(key) = &options.signing_key {
+                let key = key.clone();
+                let force = options.package.force;
+                task::spawn_blocking(move || signature::sign(&package, &key, force)).await??;
+            }
+        }
+        Ok(())
+    }
+
+    let result = inner(&input, &target, &options, progress.clone()).await;
+    progress.finish(if result.is_ok() { "done" } else { "failed" });
+    result
+}
+
+/// Checks the signature of a package.
+async fn verify(args: VerifyArgs) -> anyhow::Result<()> {
+    let VerifyArgs {
+        package,
+        pubkey,
+        signature,
+    } = args;
+
+    task::spawn_blocking(move || {
+        let key = signature::read_verifying_key(&pubkey)?;
+        let signature = signature.unwrap_or_else(|| signature::signature_path(&package));
+        signature::verify(&package, &signature, &key)?;
+        eprintln!("Signature of {} is valid", package.display());
+        Ok(())
+    })
+    .await?
+}
+
+/// Prints the JSON Schema of the configs written to packages.
+fn schema() -> anyhow::Result<()> {
+    let schema = schemars::schema_for!(CasesConfig);
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio::task::JoinSet;
+
+    use super::join_conversions;
+
+    #[tokio::test]
+    async fn partial_failure() {
+        let paths = [Path::new("A.zip"), Path::new("B.zip")];
+        let mut tasks = JoinSet::new();
+        tasks.spawn(async { anyhow::Ok((0, Err(anyhow::anyhow!("no cases")))) });
+        tasks.spawn(async { anyhow::Ok((1, Ok(()))) });
+        let err = join_conversions(tasks, &paths).await.unwrap_err();
+        assert_eq!(err.to_string(), "failed to convert 1 of 2 inputs");
+
+        let mut tasks = JoinSet::new();
+        tasks.spawn(async { anyhow::Ok((0, Ok(()))) });
+        tasks.spawn(async { anyhow::Ok((1, Ok(()))) });
+        assert!(join_conversions(tasks, &paths).await.is_ok());
+    }
+}
@@ -1,5 +1,6 @@
 use std::num::NonZero;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::model::{
@@ -7,7 +8,8 @@ use crate::model::{
     types::judge::{JudgeType, ResourceLimits, TaskType},
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Config of a problem as read by SAST-OJ.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CasesConfig {
     pub score: NonZero<u32>,
@@ -155,4 +157,31 @@ mod tests {
         }))
         .unwrap();
     }
+
+    #[test]
+    fn schema() {
+        let schema = serde_json::to_value(schemars::schema_for!(CasesConfig)).unwrap();
+        assert_eq!(
+            schema["required"],
+            json!(["score", "judge", "resourceLimits", "task"])
+        );
+
+        let defs = &schema["$defs"];
+        for (name, tag) in [("JudgeType", "judgeType"), ("TaskType", "taskType")] {
+            let variants = defs[name]["oneOf"].as_array().unwrap();
+            assert!(
+                variants
+                    .iter()
+                    .all(|variant| variant["properties"][tag].is_object()),
+                "{name}"
+            );
+        }
+        assert_eq!(
+            defs["JudgeType"]["oneOf"][1]["properties"]["judgeType"]["const"],
+            "special-judge"
+        );
+        for name in ["ResourceLimits", "Case", "Subtask"] {
+            assert!(defs[name].is_object(), "{name}");
+        }
+    }
 }
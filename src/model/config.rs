@@ -6,6 +6,11 @@ use crate::{
 };
 
 pub trait Config {
+    /// Problem id, used to name packages.
+    fn id(&self) -> Option<String> {
+        None
+    }
+
     fn score(&self) -> Result<NonZeroU32> {
         Ok(NonZeroU32::new(100).unwrap())
     }
@@ -17,7 +22,7 @@ pub trait Config {
     fn resource_limits(&self) -> Result<ResourceLimits> {
         Ok(ResourceLimits {
             time: 1000,
-            memory: 256,
+            memory: 256 * 1024,
         })
     }
 
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Serialization format of a config file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConfigFormat {
+    /// `config.toml`
+    #[default]
+    Toml,
+    /// `config.json`
+    Json,
+    /// `config.yaml`
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format of a config file from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Name of configs written in this format.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Toml => "config.toml",
+            Self::Json => "config.json",
+            Self::Yaml => "config.yaml",
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> anyhow::Result<T> {
+        Ok(match self {
+            Self::Toml => toml::from_str(text)?,
+            Self::Json => serde_json::from_str(text)?,
+            Self::Yaml => serde_yaml_ng::from_str(text)?,
+        })
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> anyhow::Result<String> {
+        Ok(match self {
+            Self::Toml => toml::to_string(value)?,
+            Self::Json => serde_json::to_string_pretty(value)? + "\n",
+            Self::Yaml => serde_yaml_ng::to_string(value)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::ValueEnum;
+
+    use super::ConfigFormat;
+    use crate::model::{
+        cases_config::CasesConfig,
+        types::judge::{Case, JudgeType, ResourceLimits, Subtask, TaskType},
+    };
+
+    #[test]
+    fn round_trip() {
+        let config = CasesConfig {
+            score: 100.try_into().unwrap(),
+            judge: JudgeType::SpecialJudge {
+                checker: "checker.cpp".into(),
+            },
+            resource_limits: ResourceLimits {
+                time: 1000,
+                memory: 262144,
+            },
+            task: TaskType::Subtask {
+                subtasks: vec![Subtask {
+                    cases: vec![Case {
+                        input: "1.in".into(),
+                        answer: "1.out".into(),
+                        score: 40.try_into().ok(),
+                    }],
+                    score: None,
+                }],
+            },
+        };
+
+        for &format in ConfigFormat::value_variants() {
+            let text = format.serialize(&config).unwrap();
+            for key in ["resourceLimits", "judgeType", "taskType"] {
+                assert!(text.contains(key), "{format:?}: {text}");
+            }
+
+            let parsed: CasesConfig = format.parse(&text).unwrap();
+            assert_eq!(format.serialize(&parsed).unwrap(), text, "{format:?}");
+        }
+    }
+}
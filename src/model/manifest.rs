@@ -0,0 +1,92 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Name of the manifest at the package root.
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+/// Sizes and SHA-256 hashes of the files in a package, written last to
+/// `manifest.toml`.
+///
+/// Paths are relative to the package root and separated by `/`.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Manifest {
+    /// Hashes of the serialized `CasesConfig`s, by path.
+    pub configs: BTreeMap<String, String>,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Hashes the content read through it.
+pub struct HashReader<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R: Read> HashReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// Returns the number of bytes read and their hex-encoded SHA-256.
+    pub fn finish(self) -> (u64, String) {
+        (self.size, hex(&self.hasher.finalize()))
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.size += len as u64;
+        Ok(len)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use super::HashReader;
+
+    #[test]
+    fn hash() {
+        let reader = HashReader::new(io::empty());
+        assert_eq!(
+            reader.finish(),
+            (
+                0,
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".into()
+            )
+        );
+
+        let mut reader = HashReader::new(&b"abc"[..]);
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(
+            reader.finish(),
+            (
+                3,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".into()
+            )
+        );
+    }
+}
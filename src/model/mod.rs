@@ -1,4 +1,6 @@
 pub mod cases_config;
 pub mod config;
+pub mod format;
+pub mod manifest;
 pub mod raw;
 pub mod types;
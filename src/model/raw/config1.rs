@@ -16,13 +16,30 @@ use crate::{
 
 #[derive(Debug, Deserialize)]
 pub struct ConfigData {
+    #[serde(default)]
+    id: Option<ProblemId>,
     #[serde(default)]
     time: CustomDuration,
     #[serde(default)]
     memory: CustomSize,
 }
 
+/// Problem id, which may be written as a number.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ProblemId {
+    Number(u64),
+    Text(String),
+}
+
 impl Config for ConfigData {
+    fn id(&self) -> Option<String> {
+        match self.id.as_ref()? {
+            ProblemId::Number(id) => Some(id.to_string()),
+            ProblemId::Text(id) => Some(id.clone()),
+        }
+    }
+
     fn resource_limits(&self) -> Result<ResourceLimits> {
         Ok(ResourceLimits {
             time: u32::try_from(self.time.as_millis())?,
@@ -0,0 +1,213 @@
+use std::{num::NonZeroU32, path::Path};
+
+use crate::{
+    error::Result,
+    model::{
+        config::Config,
+        types::{
+            duration::CustomDuration,
+            judge::{Case, JudgeType, ResourceLimits, TaskType},
+            memory_size::CustomSize,
+        },
+    },
+};
+
+/// Settings of generated configs, the [`Config`] defaults are used for unset
+/// fields.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub time: Option<CustomDuration>,
+    pub memory: Option<CustomSize>,
+    pub score: Option<NonZeroU32>,
+    pub judge: Option<JudgeType>,
+}
+
+/// Config of a problem without a config file, built from its test data.
+#[derive(Debug)]
+pub struct GeneratedConfig {
+    cases: Vec<Case>,
+    overrides: ConfigOverrides,
+}
+
+impl GeneratedConfig {
+    /// Pairs `<N>.in` with `<N>.out` files in the same directory, ordered by
+    /// directory and `N`. Returns `None` if there are no cases.
+    pub fn from_paths<'a>(
+        paths: impl IntoIterator<Item = &'a Path>,
+        overrides: ConfigOverrides,
+    ) -> Option<Self> {
+        let paths: Vec<_> = paths.into_iter().collect();
+        let mut cases: Vec<_> = paths
+            .iter()
+            .filter_map(|input| {
+                let number: u64 = input.file_stem()?.to_str()?.parse().ok()?;
+                let answer = input.with_extension("out");
+                (input.extension()? == "in" && paths.contains(&answer.as_path()))
+                    .then(|| ((input.parent(), number), input, answer))
+            })
+            .collect();
+        if cases.is_empty() {
+            return None;
+        }
+        cases.sort_by_key(|(key, ..)| *key);
+
+        Some(Self {
+            cases: cases
+                .into_iter()
+                .map(|(_, input, answer)| Case {
+                    input: input.to_path_buf(),
+                    answer,
+                    score: None,
+                })
+                .collect(),
+            overrides,
+        })
+    }
+
+    pub fn cases(&self) -> usize {
+        self.cases.len()
+    }
+}
+
+/// Provides the [`Config`] defaults for overrides that are unset.
+struct Defaults;
+
+impl Config for Defaults {
+    fn task(&self) -> Result<TaskType> {
+        unreachable!("Only the defaults are used")
+    }
+}
+
+impl Config for GeneratedConfig {
+    fn score(&self) -> Result<NonZeroU32> {
+        self.overrides.score.map_or_else(|| Defaults.score(), Ok)
+    }
+
+    fn judge(&self) -> Result<JudgeType> {
+        self.overrides
+            .judge
+            .clone()
+            .map_or_else(|| Defaults.judge(), Ok)
+    }
+
+    fn resource_limits(&self) -> Result<ResourceLimits> {
+        let defaults = Defaults.resource_limits()?;
+        Ok(ResourceLimits {
+            time: match self.overrides.time {
+                Some(time) => u32::try_from(time.as_millis())?,
+                None => defaults.time,
+            },
+            memory: self
+                .overrides
+                .memory
+                .map_or(defaults.memory, CustomSize::as_kibibyte),
+        })
+    }
+
+    fn task(&self) -> Result<TaskType> {
+        Ok(TaskType::Simple {
+            cases: self.cases.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{ConfigOverrides, GeneratedConfig};
+    use crate::model::{
+        config::Config,
+        raw::config1::ConfigData,
+        types::judge::{JudgeType, TaskType},
+    };
+
+    #[test]
+    fn pair_cases() {
+        let paths = [
+            "10.in",
+            "10.out",
+            "2.in",
+            "2.out",
+            "3.in",
+            "data/1.in",
+            "data/1.out",
+            "a.in",
+            "a.out",
+        ]
+        .map(Path::new);
+        let config = GeneratedConfig::from_paths(paths, ConfigOverrides::default()).unwrap();
+        assert_eq!(config.cases(), 3);
+
+        let TaskType::Simple { cases } = config.task().unwrap() else {
+            panic!("Generated configs are simple");
+        };
+        let inputs: Vec<_> = cases.iter().map(|case| case.input.clone()).collect();
+        assert_eq!(inputs, ["2.in", "10.in", "data/1.in"].map(PathBuf::from));
+        assert_eq!(cases[0].answer, Path::new("2.out"));
+
+        assert!(
+            GeneratedConfig::from_paths([Path::new("1.in")], ConfigOverrides::default()).is_none()
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let paths = ["1.in", "1.out"].map(Path::new);
+        let config = GeneratedConfig::from_paths(
+            paths,
+            ConfigOverrides {
+                time: Some("2s".parse().unwrap()),
+                score: Some(50.try_into().unwrap()),
+                judge: Some(JudgeType::SpecialJudge {
+                    checker: "checker.cpp".into(),
+                }),
+                ..ConfigOverrides::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(config.score().unwrap().get(), 50);
+        assert!(matches!(
+            config.judge().unwrap(),
+            JudgeType::SpecialJudge { .. }
+        ));
+        let limits = config.resource_limits().unwrap();
+        assert_eq!(limits.time, 2000);
+        assert_eq!(limits.memory, 256 * 1024);
+
+        let config = GeneratedConfig::from_paths(
+            paths,
+            ConfigOverrides {
+                memory: Some("512MiB".parse().unwrap()),
+                ..ConfigOverrides::default()
+            },
+        )
+        .unwrap();
+        let limits = config.resource_limits().unwrap();
+        assert_eq!(limits.time, 1000);
+        assert_eq!(limits.memory, 512 * 1024);
+    }
+
+    #[test]
+    fn same_memory_unit() {
+        let converted: ConfigData = serde_yaml_ng::from_str("memory: 256").unwrap();
+        let generated = GeneratedConfig::from_paths(
+            ["1.in", "1.out"].map(Path::new),
+            ConfigOverrides {
+                memory: Some("256MiB".parse().unwrap()),
+                ..ConfigOverrides::default()
+            },
+        )
+        .unwrap();
+        let default = GeneratedConfig::from_paths(
+            ["1.in", "1.out"].map(Path::new),
+            ConfigOverrides::default(),
+        )
+        .unwrap();
+
+        let memory = |config: &dyn Config| config.resource_limits().unwrap().memory;
+        assert_eq!(memory(&converted), memory(&generated));
+        assert_eq!(memory(&default), memory(&generated));
+    }
+}
@@ -1 +1,2 @@
 pub mod config1;
+pub mod generated;
@@ -36,6 +36,15 @@ impl Deref for CustomDuration {
     }
 }
 
+impl FromStr for CustomDuration {
+    type Err = serde::de::value::Error;
+
+    /// Parses a duration like in configs, e.g. `1`, `1.5s` or `500ms`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DurationVisitor.visit_str(s)
+    }
+}
+
 impl<'de> Deserialize<'de> for CustomDuration {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
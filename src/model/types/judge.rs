@@ -1,8 +1,9 @@
 use std::{num::NonZeroU32, path::PathBuf};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 #[serde(tag = "judgeType")]
 pub enum JudgeType {
@@ -11,13 +12,13 @@ pub enum JudgeType {
     Interactive { interactor: PathBuf },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct ResourceLimits {
     pub time: u32,   // ms
-    pub memory: u32, // MiB
+    pub memory: u32, // KiB
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 #[serde(tag = "taskType")]
 pub enum TaskType {
@@ -25,7 +26,7 @@ pub enum TaskType {
     Subtask { subtasks: Vec<Subtask> },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct Case {
     pub input: PathBuf,
     pub answer: PathBuf,
@@ -34,7 +35,7 @@ pub struct Case {
     pub score: Option<NonZeroU32>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Subtask {
     pub cases: Vec<Case>,
 
@@ -76,44 +76,109 @@ impl FromStr for MemorySizeUnit {
     }
 }
 
+impl FromStr for CustomSize {
+    type Err = de::value::Error;
+
+    /// Parses a memory size like in configs, e.g. `256`, `1.5GiB` or
+    /// `512MiB`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CustomSizeVisitor.visit_str(s)
+    }
+}
+
 impl<'de> Deserialize<'de> for CustomSize {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        struct CustomSizeVisitor;
-
-        impl Visitor<'_> for CustomSizeVisitor {
-            type Value = CustomSize;
-
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("a value representing memory size, between 64MiB and 10GiB (10,240MiB), in MiB or GiB")
-            }
-
-            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
-            where
-                E: Error,
-            {
-                match v {
-                    1..=10 => Ok(CustomSize(Size::from_gibibytes(v))),
-                    64..=10240 => Ok(CustomSize(Size::from_mebibytes(v))),
-                    _ => Err(Error::invalid_value(Unexpected::Unsigned(v), &self)),
-                }
-            }
-
-            /// Deserialize f64, may cause
-            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
-            where
-                E: de::Error,
-            {
-                match v {
-                    0.1..=10.0 => Ok(CustomSize(Size::from_gibibytes(v))),
-                    64.0..=10240.0 => Ok(CustomSize(Size::from_mebibytes(v))),
-                    _ => Err(Error::invalid_value(Unexpected::Float(v), &self)),
-                }
-            }
+        deserializer.deserialize_any(CustomSizeVisitor)
+    }
+}
+
+struct CustomSizeVisitor;
+
+impl Visitor<'_> for CustomSizeVisitor {
+    type Value = CustomSize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(
+            "a value representing memory size, between 64MiB and 10GiB (10,240MiB), in MiB or GiB",
+        )
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        match v {
+            1..=10 => Ok(CustomSize(Size::from_gibibytes(v))),
+            64..=10240 => Ok(CustomSize(Size::from_mebibytes(v))),
+            _ => Err(Error::invalid_value(Unexpected::Unsigned(v), &self)),
+        }
+    }
+
+    /// Deserialize f64, may cause
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match v {
+            0.1..=10.0 => Ok(CustomSize(Size::from_gibibytes(v))),
+            64.0..=10240.0 => Ok(CustomSize(Size::from_mebibytes(v))),
+            _ => Err(Error::invalid_value(Unexpected::Float(v), &self)),
         }
+    }
 
-        deserializer.deserialize_any(CustomSizeVisitor)
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        let (value, unit) = v.split_at(
+            v.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(v.len()),
+        );
+        let value: f64 = value.parse().map_err(Error::custom)?;
+
+        let size = match MemorySizeUnit::from_str(unit).map_err(Error::custom)? {
+            MemorySizeUnit::Unspecified => return self.visit_f64(value),
+            MemorySizeUnit::Kibibyte => Size::from_kibibytes(value),
+            MemorySizeUnit::Mebibyte => Size::from_mebibytes(value),
+            MemorySizeUnit::Gibibyte => Size::from_gibibytes(value),
+        };
+
+        let bytes = size.bytes();
+        if bytes < Size::from_mebibytes(64).bytes() || bytes > Size::from_gibibytes(10).bytes() {
+            return Err(Error::invalid_value(Unexpected::Str(v), &self));
+        }
+        Ok(CustomSize(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use size::Size;
+
+    use super::CustomSize;
+
+    #[test]
+    fn parse() {
+        let cases = [
+            ("256", Size::from_mebibytes(256)),
+            ("2", Size::from_gibibytes(2)),
+            ("512MiB", Size::from_mebibytes(512)),
+            ("1.5GiB", Size::from_gibibytes(1.5)),
+            ("65536kb", Size::from_kibibytes(65536)),
+            ("10GiB", Size::from_gibibytes(10)),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.parse::<CustomSize>().unwrap(), CustomSize(expected));
+        }
+
+        for value in ["20", "256TiB", "MiB", "", "32MiB", "1024kb", "11GiB"] {
+            assert!(value.parse::<CustomSize>().is_err(), "{value}");
+        }
+
+        let size: CustomSize = serde_json::from_str("\"256MiB\"").unwrap();
+        assert_eq!(size.as_kibibyte(), 256 * 1024);
     }
 }
@@ -0,0 +1,209 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write},
+    path::Path,
+};
+
+use tempfile::NamedTempFile;
+
+/// Options for normalizing test data.
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeOptions {
+    /// Also strip spaces and tabs at the end of lines.
+    pub trim_trailing: bool,
+}
+
+/// What was changed in a file by [`normalize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub bom: bool,
+    pub crlf: bool,
+    pub trailing_whitespace: bool,
+    pub final_newline: bool,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changes = [
+            (self.bom, "removed BOM"),
+            (self.crlf, "CRLF to LF"),
+            (self.trailing_whitespace, "trimmed trailing whitespace"),
+            (self.final_newline, "added final newline"),
+        ];
+        let mut changes = changes
+            .into_iter()
+            .filter_map(|(changed, description)| changed.then_some(description));
+
+        if let Some(first) = changes.next() {
+            f.write_str(first)?;
+            for change in changes {
+                write!(f, ", {change}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Files with a NUL byte in this many leading bytes are considered binary.
+const BINARY_SNIFF_LEN: u64 = 8 * 1024;
+
+/// Normalizes the text file at `path` in place.
+///
+/// Returns `None` if the file looks binary and was left untouched.
+pub fn normalize_file(path: &Path, options: &NormalizeOptions) -> io::Result<Option<Changes>> {
+    let mut file = File::open(path)?;
+
+    let mut head = Vec::new();
+    (&mut file).take(BINARY_SNIFF_LEN).read_to_end(&mut head)?;
+    if head.contains(&0) {
+        return Ok(None);
+    }
+    file.rewind()?;
+
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let mut temp = NamedTempFile::new_in(parent)?;
+    let mut writer = BufWriter::new(temp.as_file_mut());
+    let changes = normalize(&mut BufReader::new(file), &mut writer, options)?;
+    writer.flush()?;
+    drop(writer);
+
+    if !changes.is_empty() {
+        temp.persist(path)?;
+    }
+
+    Ok(Some(changes))
+}
+
+/// Copies text from `reader` to `writer`, converting line endings to LF,
+/// stripping a UTF-8 BOM and making sure the text ends with a newline.
+pub fn normalize(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    options: &NormalizeOptions,
+) -> io::Result<Changes> {
+    let mut changes = Changes::default();
+
+    if reader.fill_buf()?.starts_with(BOM) {
+        reader.consume(BOM.len());
+        changes.bom = true;
+    }
+
+    let mut pending_cr = false;
+    let mut whitespace = Vec::new();
+    let mut empty = true;
+    let mut at_line_start = true;
+
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+
+        for &byte in buf {
+            empty = false;
+
+            if pending_cr {
+                pending_cr = false;
+                if byte == b'\n' {
+                    changes.crlf = true;
+                } else {
+                    writer.write_all(&whitespace)?;
+                    whitespace.clear();
+                    writer.write_all(b"\r")?;
+                    at_line_start = false;
+                }
+            }
+
+            match byte {
+                b'\r' => pending_cr = true,
+                b'\n' => {
+                    changes.trailing_whitespace |= !whitespace.is_empty();
+                    whitespace.clear();
+                    writer.write_all(b"\n")?;
+                    at_line_start = true;
+                }
+                b' ' | b'\t' if options.trim_trailing => whitespace.push(byte),
+                _ => {
+                    writer.write_all(&whitespace)?;
+                    whitespace.clear();
+                    writer.write_all(&[byte])?;
+                    at_line_start = false;
+                }
+            }
+        }
+
+        let len = buf.len();
+        reader.consume(len);
+    }
+
+    // A CR right before the end of the file is a line ending cut short.
+    changes.crlf |= pending_cr;
+    changes.trailing_whitespace |= !whitespace.is_empty();
+
+    if !empty && (pending_cr || !at_line_start) {
+        changes.final_newline |= !pending_cr;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Changes, NormalizeOptions, normalize};
+
+    fn run(input: &[u8], trim_trailing: bool) -> (String, Changes) {
+        let mut output = Vec::new();
+        let changes = normalize(
+            &mut &input[..],
+            &mut output,
+            &NormalizeOptions { trim_trailing },
+        )
+        .unwrap();
+        (String::from_utf8(output).unwrap(), changes)
+    }
+
+    #[test]
+    fn normalize_text() {
+        assert_eq!(
+            run(b"1 2\n3\n", false),
+            ("1 2\n3\n".into(), Changes::default())
+        );
+        assert_eq!(run(b"", false), ("".into(), Changes::default()));
+
+        let (text, changes) = run(b"\xEF\xBB\xBF1 2\r\n3", false);
+        assert_eq!(text, "1 2\n3\n");
+        assert_eq!(
+            changes,
+            Changes {
+                bom: true,
+                crlf: true,
+                final_newline: true,
+                ..Changes::default()
+            }
+        );
+
+        let (text, changes) = run(b"1 2 \t\r\n3\r\n \n", true);
+        assert_eq!(text, "1 2\n3\n\n");
+        assert_eq!(
+            changes,
+            Changes {
+                crlf: true,
+                trailing_whitespace: true,
+                ..Changes::default()
+            }
+        );
+
+        assert_eq!(run(b"1 2 \n", false).0, "1 2 \n");
+        assert_eq!(run(b"a\rb\r", false).0, "a\rb\n");
+    }
+}
@@ -0,0 +1,596 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use tar::{EntryType, Header};
+use tempfile::{TempDir, TempPath};
+use time::{OffsetDateTime, PrimitiveDateTime};
+use xz2::write::XzEncoder;
+use zip::{CompressionMethod, DateTime, ZipWriter, write::SimpleFileOptions};
+
+use crate::{compression::Compression, error::Error, progress::Progress};
+
+/// How packages are written.
+#[derive(Debug, Clone)]
+pub struct PackageOptions {
+    pub format: OutputFormat,
+    pub compression: Compression,
+    /// Top-level directory inside packages.
+    pub root: String,
+    /// Replace existing packages.
+    pub force: bool,
+}
+
+impl PackageOptions {
+    /// Creates the package at `path`, counting the bytes written in
+    /// `progress`.
+    ///
+    /// Archives are reproducible: entries are stored in the order they are
+    /// added, with their modification time set to [`source_date_epoch`] and
+    /// fixed ownership and modes. `compression` only applies to
+    /// [`OutputFormat::TarZst`]. A directory package takes the place of the
+    /// root directory.
+    ///
+    /// The package is written to a temporary path next to `path` and only
+    /// moved into place once it is finished, see [`Output`].
+    pub fn create(&self, path: &Path, progress: &Progress) -> anyhow::Result<Box<dyn Package>> {
+        self.check_output(path)?;
+        let parent = path.parent().unwrap_or(Path::new("."));
+        let mut temp = tempfile::Builder::new();
+        let prefix = format!(
+            ".{}.",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        temp.prefix(&prefix).suffix(".tmp");
+        #[cfg(unix)]
+        temp.permissions(default_permissions(self.format == OutputFormat::Dir));
+
+        if self.format == OutputFormat::Dir {
+            let temp_dir = temp.tempdir_in(parent)?;
+            progress.bytes("writing");
+            let package = DirPackage {
+                dir: temp_dir.path().to_path_buf(),
+                root: PathBuf::from(&self.root),
+                progress: progress.clone(),
+            };
+            return Ok(Box::new(Atomic {
+                package: Box::new(package),
+                output: Output::Dir(temp_dir),
+                path: path.to_path_buf(),
+                force: self.force,
+            }));
+        }
+
+        let mtime = source_date_epoch()?;
+        let (file, temp_path) = temp.tempfile_in(parent)?.into_parts();
+        let file = progress.wrap_write(file);
+        let package: Box<dyn Package> = match self.format {
+            OutputFormat::TarZst => {
+                Box::new(TarPackage::new(self.compression.encoder(file)?, mtime))
+            }
+            OutputFormat::TarGz => Box::new(TarPackage::new(
+                GzEncoder::new(file, flate2::Compression::default()),
+                mtime,
+            )),
+            OutputFormat::TarXz => Box::new(TarPackage::new(XzEncoder::new(file, 6), mtime)),
+            OutputFormat::Zip => Box::new(ZipPackage::new(file, mtime)),
+            OutputFormat::Tar => Box::new(TarPackage::new(file, mtime)),
+            OutputFormat::Dir => unreachable!("Directories are handled above"),
+        };
+        progress.bytes(if self.format == OutputFormat::Tar {
+            "writing"
+        } else {
+            "compressing"
+        });
+        Ok(Box::new(Atomic {
+            package,
+            output: Output::File(temp_path),
+            path: path.to_path_buf(),
+            force: self.force,
+        }))
+    }
+
+    /// Fails if a package already exists at `path` and may not be replaced.
+    pub fn check_output(&self, path: &Path) -> crate::error::Result<()> {
+        if !self.force && fs::exists(path)? {
+            Err(Error::OutputExists(path.to_path_buf()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for PackageOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::default(),
+            compression: Compression::default(),
+            root: String::from("config"),
+            force: false,
+        }
+    }
+}
+
+/// Permissions of new files or directories before the umask is applied, like
+/// `File::create` and `fs::create_dir` use, as `tempfile` restricts them to
+/// the owner.
+#[cfg(unix)]
+pub fn default_permissions(dir: bool) -> fs::Permissions {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::Permissions::from_mode(if dir { 0o777 } else { 0o666 })
+}
+
+/// Container of generated packages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// `.tar.zst`, as read by SAST-OJ
+    #[default]
+    TarZst,
+    /// `.tar.gz`
+    TarGz,
+    /// `.tar.xz`
+    TarXz,
+    /// `.zip`
+    Zip,
+    /// `.tar`, uncompressed
+    Tar,
+    /// A plain directory
+    Dir,
+}
+
+impl OutputFormat {
+    /// File name of a package called `name`.
+    pub fn file_name(self, name: &str) -> String {
+        match self {
+            Self::TarZst => format!("{name}.tar.zst"),
+            Self::TarGz => format!("{name}.tar.gz"),
+            Self::TarXz => format!("{name}.tar.xz"),
+            Self::Zip => format!("{name}.zip"),
+            Self::Tar => format!("{name}.tar"),
+            Self::Dir => name.to_owned(),
+        }
+    }
+}
+
+/// A package being written, entries are added parents first.
+pub trait Package: Send {
+    fn add_dir(&mut self, name: &Path) -> anyhow::Result<()>;
+
+    /// Adds a file of `size` bytes read from `reader`.
+    fn add_file(&mut self, name: &Path, size: u64, reader: &mut dyn Read) -> anyhow::Result<()>;
+
+    /// Writes the remaining data, such as trailers and compression frames.
+    fn finish(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+/// A package written to a temporary path, removed on drop.
+enum Output {
+    File(TempPath),
+    Dir(TempDir),
+}
+
+/// Moves a package into place once it is finished.
+struct Atomic {
+    package: Box<dyn Package>,
+    output: Output,
+    path: PathBuf,
+    force: bool,
+}
+
+impl Package for Atomic {
+    fn add_dir(&mut self, name: &Path) -> anyhow::Result<()> {
+        self.package.add_dir(name)
+    }
+
+    fn add_file(&mut self, name: &Path, size: u64, reader: &mut dyn Read) -> anyhow::Result<()> {
+        self.package.add_file(name, size, reader)
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<()> {
+        let Self {
+            package,
+            output,
+            path,
+            force,
+        } = *self;
+        package.finish()?;
+
+        match output {
+            Output::File(temp_path) => persist(temp_path, &path, force)?,
+            Output::Dir(temp_dir) => {
+                if fs::exists(&path)? {
+                    if !force {
+                        Err(Error::OutputExists(path.clone()))?;
+                    }
+                    // Directories can't be replaced atomically.
+                    fs::remove_dir_all(&path)?;
+                }
+                fs::rename(temp_dir.keep(), &path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Moves a finished temporary file to `path`, replacing an existing file only
+/// if `force` is set.
+pub fn persist(temp_path: TempPath, path: &Path, force: bool) -> anyhow::Result<()> {
+    if force {
+        temp_path.persist(path)?;
+    } else {
+        temp_path
+            .persist_noclobber(path)
+            .map_err(|e| match e.error.kind() {
+                io::ErrorKind::AlreadyExists => Error::OutputExists(path.to_path_buf()).into(),
+                _ => anyhow::Error::from(e),
+            })?;
+    }
+    Ok(())
+}
+
+/// A writer that must be finished explicitly to be complete.
+trait Finish: Write {
+    fn finish(self) -> io::Result<()>;
+}
+
+impl<W: Write> Finish for zstd::Encoder<'_, W> {
+    fn finish(self) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl<W: Write> Finish for GzEncoder<W> {
+    fn finish(self) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl<W: Write> Finish for XzEncoder<W> {
+    fn finish(self) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl<W: Write> Finish for indicatif::ProgressBarIter<W> {
+    fn finish(mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Returns the timestamp recorded in packages, from the `SOURCE_DATE_EPOCH`
+/// environment variable or `0` if it is unset.
+///
+/// See <https://reproducible-builds.org/specs/source-date-epoch/>.
+pub fn source_date_epoch() -> crate::error::Result<u64> {
+    parse_source_date_epoch(env::var("SOURCE_DATE_EPOCH").ok().as_deref())
+}
+
+fn parse_source_date_epoch(value: Option<&str>) -> crate::error::Result<u64> {
+    match value {
+        None | Some("") => Ok(0),
+        Some(value) => value
+            .parse()
+            .map_err(|_| Error::SourceDateEpoch(value.to_owned())),
+    }
+}
+
+struct TarPackage<W: Write> {
+    builder: tar::Builder<W>,
+    mtime: u64,
+}
+
+impl<W: Write> TarPackage<W> {
+    fn new(writer: W, mtime: u64) -> Self {
+        Self {
+            builder: tar::Builder::new(writer),
+            mtime,
+        }
+    }
+
+    fn header(&self, entry_type: EntryType, size: u64) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(if entry_type.is_dir() { 0o755 } else { 0o644 });
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(self.mtime);
+        header
+    }
+}
+
+impl<W: Finish + Send> Package for TarPackage<W> {
+    fn add_dir(&mut self, name: &Path) -> anyhow::Result<()> {
+        let mut header = self.header(EntryType::Directory, 0);
+        self.builder.append_data(&mut header, name, io::empty())?;
+        Ok(())
+    }
+
+    fn add_file(&mut self, name: &Path, size: u64, reader: &mut dyn Read) -> anyhow::Result<()> {
+        let mut header = self.header(EntryType::Regular, size);
+        self.builder.append_data(&mut header, name, reader)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<()> {
+        self.builder.into_inner()?.finish()?;
+        Ok(())
+    }
+}
+
+struct ZipPackage<W: Write + Seek> {
+    writer: ZipWriter<W>,
+    modified: DateTime,
+}
+
+impl<W: Write + Seek> ZipPackage<W> {
+    fn new(writer: W, mtime: u64) -> Self {
+        // ZIP timestamps start in 1980, earlier times are clamped to it.
+        let modified = i64::try_from(mtime)
+            .ok()
+            .and_then(|mtime| OffsetDateTime::from_unix_timestamp(mtime).ok())
+            .and_then(|mtime| {
+                DateTime::try_from(PrimitiveDateTime::new(mtime.date(), mtime.time())).ok()
+            })
+            .unwrap_or_default();
+        Self {
+            writer: ZipWriter::new(writer),
+            modified,
+        }
+    }
+
+    fn options(&self, mode: u32) -> SimpleFileOptions {
+        SimpleFileOptions::default()
+            .last_modified_time(self.modified)
+            .unix_permissions(mode)
+    }
+}
+
+impl<W: Write + Seek + Send> Package for ZipPackage<W> {
+    fn add_dir(&mut self, name: &Path) -> anyhow::Result<()> {
+        let options = self.options(0o755);
+        self.writer.add_directory(entry_name(name)?, options)?;
+        Ok(())
+    }
+
+    fn add_file(&mut self, name: &Path, size: u64, reader: &mut dyn Read) -> anyhow::Result<()> {
+        let options = self
+            .options(0o644)
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(size >= u64::from(u32::MAX));
+        self.writer.start_file(entry_name(name)?, options)?;
+        io::copy(reader, &mut self.writer)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<()> {
+        self.writer.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+/// Converts `name` to a portable entry name separated by `/`, as in ZIP
+/// archives and manifests.
+pub fn entry_name(name: &Path) -> crate::error::Result<String> {
+    let components = name
+        .iter()
+        .map(|component| component.to_str())
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| Error::NonUtf8Name(name.to_path_buf()))?;
+    Ok(components.join("/"))
+}
+
+/// Writes a package as a plain directory tree.
+struct DirPackage {
+    /// Directory written in place of the root directory.
+    dir: PathBuf,
+    root: PathBuf,
+    progress: Progress,
+}
+
+impl DirPackage {
+    fn path(&self, name: &Path) -> PathBuf {
+        self.dir.join(name.strip_prefix(&self.root).unwrap_or(name))
+    }
+}
+
+impl Package for DirPackage {
+    fn add_dir(&mut self, name: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(self.path(name))?;
+        Ok(())
+    }
+
+    fn add_file(&mut self, name: &Path, _size: u64, reader: &mut dyn Read) -> anyhow::Result<()> {
+        let mut file = self.progress.wrap_write(File::create(self.path(name))?);
+        io::copy(reader, &mut file)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read, path::Path};
+
+    use tempfile::tempdir;
+
+    use super::{OutputFormat, PackageOptions, parse_source_date_epoch};
+    use crate::{error::Error, progress::Progress};
+
+    fn write(format: OutputFormat, dir: &Path) -> anyhow::Result<()> {
+        let options = PackageOptions {
+            format,
+            ..PackageOptions::default()
+        };
+        let mut package =
+            options.create(&dir.join(format.file_name("config")), &Progress::default())?;
+        package.add_dir(Path::new("config"))?;
+        package.add_dir(Path::new("config/data"))?;
+        package.add_file(Path::new("config/data/1.in"), 4, &mut &b"1 2\n"[..])?;
+        package.finish()
+    }
+
+    fn tar_entries(reader: impl Read) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        for entry in tar::Archive::new(reader).entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            entries.push((name, content));
+        }
+        Ok(entries)
+    }
+
+    #[test]
+    fn formats() -> anyhow::Result<()> {
+        let expected = [
+            ("config".to_owned(), Vec::new()),
+            ("config/data".to_owned(), Vec::new()),
+            ("config/data/1.in".to_owned(), b"1 2\n".to_vec()),
+        ];
+
+        let dir = tempdir()?;
+        for format in [
+            OutputFormat::TarZst,
+            OutputFormat::TarGz,
+            OutputFormat::TarXz,
+            OutputFormat::Tar,
+        ] {
+            write(format, dir.path())?;
+            let file = File::open(dir.path().join(format.file_name("config")))?;
+            let reader: Box<dyn Read> = match format {
+                OutputFormat::TarZst => Box::new(zstd::Decoder::new(file)?),
+                OutputFormat::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+                OutputFormat::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+                _ => Box::new(file),
+            };
+            assert_eq!(tar_entries(reader)?, expected, "{format:?}");
+        }
+
+        write(OutputFormat::Zip, dir.path())?;
+        let mut zip = zip::ZipArchive::new(File::open(dir.path().join("config.zip"))?)?;
+        assert!(zip.by_name("config/data/")?.is_dir());
+        let mut content = String::new();
+        zip.by_name("config/data/1.in")?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "1 2\n");
+
+        write(OutputFormat::Dir, dir.path())?;
+        assert_eq!(
+            std::fs::read(dir.path().join("config/data/1.in"))?,
+            b"1 2\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reproducible() -> anyhow::Result<()> {
+        let (first, second) = (tempdir()?, tempdir()?);
+        for format in [
+            OutputFormat::TarZst,
+            OutputFormat::TarGz,
+            OutputFormat::TarXz,
+            OutputFormat::Zip,
+            OutputFormat::Tar,
+        ] {
+            write(format, first.path())?;
+            write(format, second.path())?;
+            let name = format.file_name("config");
+            assert_eq!(
+                std::fs::read(first.path().join(&name))?,
+                std::fs::read(second.path().join(&name))?,
+                "{format:?}"
+            );
+        }
+
+        let mut archive = tar::Archive::new(File::open(first.path().join("config.tar"))?);
+        for entry in archive.entries()? {
+            let header = entry?.header().clone();
+            assert_eq!(header.mtime()?, 0);
+            assert_eq!((header.uid()?, header.gid()?), (0, 0));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn source_date_epoch() {
+        assert_eq!(parse_source_date_epoch(None).unwrap(), 0);
+        assert_eq!(parse_source_date_epoch(Some("")).unwrap(), 0);
+        assert_eq!(
+            parse_source_date_epoch(Some("1700000000")).unwrap(),
+            1_700_000_000
+        );
+        assert!(matches!(
+            parse_source_date_epoch(Some("yesterday")),
+            Err(Error::SourceDateEpoch(_))
+        ));
+    }
+
+    #[test]
+    fn atomic() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("config.tar");
+        let options = PackageOptions {
+            format: OutputFormat::Tar,
+            ..PackageOptions::default()
+        };
+
+        let mut package = options.create(&path, &Progress::default())?;
+        package.add_dir(Path::new("config"))?;
+        drop(package);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+
+        write(OutputFormat::Tar, dir.path())?;
+        assert!(matches!(
+            options
+                .create(&path, &Progress::default())
+                .err()
+                .unwrap()
+                .downcast_ref(),
+            Some(Error::OutputExists(_))
+        ));
+
+        let options = PackageOptions {
+            force: true,
+            ..options
+        };
+        options.create(&path, &Progress::default())?.finish()?;
+        assert_eq!(std::fs::metadata(&path)?.len(), 1024);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = |path: &Path| -> std::io::Result<u32> {
+                Ok(std::fs::metadata(path)?.permissions().mode() & 0o777)
+            };
+            let reference = dir.path().join("reference");
+            File::create(&reference)?;
+            assert_eq!(mode(&path)?, mode(&reference)?);
+
+            let options = PackageOptions {
+                format: OutputFormat::Dir,
+                ..PackageOptions::default()
+            };
+            options
+                .create(&dir.path().join("out"), &Progress::default())?
+                .finish()?;
+            std::fs::create_dir(dir.path().join("reference-dir"))?;
+            assert_eq!(
+                mode(&dir.path().join("out"))?,
+                mode(&dir.path().join("reference-dir"))?
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal as _, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressBarIter, ProgressDrawTarget, ProgressStyle};
+
+/// Progress bars of all conversions, drawn on stderr.
+///
+/// Bars are hidden when stderr is not a terminal.
+#[derive(Debug, Clone)]
+pub struct Bars {
+    multi: MultiProgress,
+}
+
+impl Bars {
+    pub fn new() -> Self {
+        let target = if io::stderr().is_terminal() {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+
+        Self {
+            multi: MultiProgress::with_draw_target(target),
+        }
+    }
+
+    /// Adds a bar for the conversion of the input called `name`.
+    pub fn add(&self, name: impl Into<String>) -> Progress {
+        let bar = self.multi.add(ProgressBar::no_length());
+        bar.set_prefix(name.into());
+        if !bar.is_hidden() {
+            bar.enable_steady_tick(Duration::from_millis(100));
+        }
+        Progress {
+            bar,
+            stage: Arc::default(),
+        }
+    }
+}
+
+/// Progress of the conversion of one input.
+///
+/// Messages are printed above the bars, or as plain lines when the bars are
+/// hidden. The default is hidden.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    bar: ProgressBar,
+    stage: Arc<Mutex<&'static str>>,
+}
+
+impl Progress {
+    const BYTES_TEMPLATE: &str =
+        "{spinner} {prefix:.bold} {msg} {binary_bytes} ({binary_bytes_per_sec})";
+    const COUNT_TEMPLATE: &str = "{spinner} {prefix:.bold} {msg} {pos}/{len}";
+
+    fn start(&self, template: &str, stage: &'static str, len: Option<u64>) {
+        *self.stage.lock().expect("Stage lock poisoned") = stage;
+        let style = ProgressStyle::with_template(template).expect("Valid template");
+        self.bar.set_style(style);
+        self.bar.set_message(stage);
+        self.bar.reset();
+        match len {
+            Some(len) => self.bar.set_length(len),
+            None => self.bar.unset_length(),
+        }
+    }
+
+    /// Starts counting bytes, e.g. while extracting or compressing.
+    pub fn bytes(&self, stage: &'static str) {
+        self.start(Self::BYTES_TEMPLATE, stage, None);
+    }
+
+    /// Starts counting `len` steps, e.g. config files to convert.
+    pub fn steps(&self, stage: &'static str, len: usize) {
+        self.start(Self::COUNT_TEMPLATE, stage, Some(len as u64));
+    }
+
+    /// Shows the number of entries seen in the current stage.
+    pub fn entries(&self, count: usize) {
+        if self.bar.is_hidden() {
+            return;
+        }
+        let stage = *self.stage.lock().expect("Stage lock poisoned");
+        self.bar.set_message(format!("{stage}, {count} entries"));
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+    }
+
+    /// Wraps `writer` to count the bytes written through it.
+    pub fn wrap_write<W: Write>(&self, writer: W) -> ProgressBarIter<W> {
+        self.bar.wrap_write(writer)
+    }
+
+    pub fn println(&self, message: impl Display) {
+        if self.bar.is_hidden() {
+            eprintln!("{message}");
+        } else {
+            self.bar.println(message.to_string());
+        }
+    }
+
+    pub fn finish(&self, message: &'static str) {
+        self.bar.set_style(
+            ProgressStyle::with_template("  {prefix:.bold} {msg}").expect("Valid template"),
+        );
+        self.bar.finish_with_message(message);
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            bar: ProgressBar::hidden(),
+            stage: Arc::default(),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use ed25519_dalek::{
+    SIGNATURE_LENGTH, Signature, SigningKey, VerifyingKey,
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+};
+use sha2::{Digest, Sha512};
+
+use crate::{error::Error, package};
+
+/// Context of package signatures, so they can't be mistaken for signatures
+/// of other data.
+const CONTEXT: &[u8] = b"yarusto package";
+
+/// Reads a PKCS#8 PEM private key, e.g. from
+/// `openssl genpkey -algorithm ed25519`.
+pub fn read_signing_key(path: &Path) -> anyhow::Result<SigningKey> {
+    let pem = fs::read_to_string(path)?;
+    SigningKey::from_pkcs8_pem(&pem).map_err(|_| Error::InvalidKey(path.to_path_buf()).into())
+}
+
+/// Reads a PEM public key, e.g. from `openssl pkey -pubout`.
+pub fn read_verifying_key(path: &Path) -> anyhow::Result<VerifyingKey> {
+    let pem = fs::read_to_string(path)?;
+    VerifyingKey::from_public_key_pem(&pem)
+        .map_err(|_| Error::InvalidKey(path.to_path_buf()).into())
+}
+
+/// Returns the path of the detached signature of the package at `path`.
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(OsString::from(".sig"));
+    PathBuf::from(name)
+}
+
+/// Signs the package at `path` with Ed25519ph, writing the raw signature next
+/// to it, see [`signature_path`].
+///
+/// Like packages, an existing signature is only replaced if `force` is set.
+pub fn sign(path: &Path, key: &SigningKey, force: bool) -> anyhow::Result<PathBuf> {
+    let signature = key.sign_prehashed(prehash(path)?, Some(CONTEXT))?;
+    let signature_path = signature_path(path);
+
+    let mut temp = tempfile::Builder::new();
+    #[cfg(unix)]
+    temp.permissions(package::default_permissions(false));
+    let mut temp = temp.tempfile_in(path.parent().unwrap_or(Path::new(".")))?;
+    temp.write_all(&signature.to_bytes())?;
+    package::persist(temp.into_temp_path(), &signature_path, force)?;
+    Ok(signature_path)
+}
+
+/// Checks the detached signature at `signature_path` of the package at
+/// `path`.
+pub fn verify(path: &Path, signature_path: &Path, key: &VerifyingKey) -> anyhow::Result<()> {
+    let bytes = fs::read(signature_path)?;
+    let bytes: &[u8; SIGNATURE_LENGTH] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::InvalidSignature(signature_path.to_path_buf()))?;
+    let signature = Signature::from_bytes(bytes);
+
+    key.verify_prehashed_strict(prehash(path)?, Some(CONTEXT), &signature)
+        .map_err(|_| Error::SignatureMismatch(path.to_path_buf()))?;
+    Ok(())
+}
+
+fn prehash(path: &Path) -> io::Result<Sha512> {
+    let mut hasher = Sha512::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use ed25519_dalek::SigningKey;
+    use tempfile::tempdir;
+
+    use super::{sign, signature_path, verify};
+    use crate::error::Error;
+
+    #[test]
+    fn sign_and_verify() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let package = dir.path().join("config.tar.zst");
+        fs::write(&package, b"package")?;
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let signature = sign(&package, &key, false)?;
+        assert_eq!(signature, dir.path().join("config.tar.zst.sig"));
+        assert_eq!(signature_path(Path::new("a.zip")), Path::new("a.zip.sig"));
+        verify(&package, &signature, &key.verifying_key())?;
+
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let error = verify(&package, &signature, &other.verifying_key()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(Error::SignatureMismatch(_))
+        ));
+
+        fs::write(&package, b"altered")?;
+        let error = verify(&package, &signature, &key.verifying_key()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(Error::SignatureMismatch(_))
+        ));
+
+        let error = sign(&package, &key, false).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(Error::OutputExists(_))));
+        sign(&package, &key, true)?;
+        verify(&package, &signature, &key.verifying_key())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = |path: &Path| -> std::io::Result<u32> {
+                Ok(fs::metadata(path)?.permissions().mode() & 0o777)
+            };
+            assert_eq!(mode(&signature)?, mode(&package)?);
+        }
+
+        fs::write(&signature, b"short")?;
+        let error = verify(&package, &signature, &key.verifying_key()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(Error::InvalidSignature(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    fmt,
+    path::{Component, Path},
+    str::FromStr,
+};
+
+use crate::error::Error;
+
+/// Template of package names, e.g. `{contest}-{stem}`.
+///
+/// The extension of the output format is appended to the rendered name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// Name of the input without its archive extension.
+    Stem,
+    /// Name of the directory searched for inputs, or containing the input.
+    Contest,
+    /// Problem id from the config.
+    Id,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// Values of the placeholders of a [`NameTemplate`].
+#[derive(Debug)]
+pub struct NameValues<'a> {
+    pub stem: &'a str,
+    pub contest: Option<&'a str>,
+    pub id: Option<&'a str>,
+}
+
+impl NameTemplate {
+    /// Checks whether the template refers to the problem id, which is only
+    /// known after the config is converted.
+    pub fn uses_id(&self) -> bool {
+        self.parts.contains(&Part::Placeholder(Placeholder::Id))
+    }
+
+    pub fn render(&self, values: &NameValues) -> crate::error::Result<String> {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Placeholder(placeholder) => {
+                    let value = match placeholder {
+                        Placeholder::Stem => Some(values.stem),
+                        Placeholder::Contest => values.contest,
+                        Placeholder::Id => values.id,
+                    };
+                    name.push_str(value.ok_or(Error::MissingName(placeholder.name()))?);
+                }
+            }
+        }
+        check_name(&name)?;
+        Ok(name)
+    }
+}
+
+impl Placeholder {
+    const ALL: [Self; 3] = [Self::Stem, Self::Contest, Self::Id];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Stem => "stem",
+            Self::Contest => "contest",
+            Self::Id => "id",
+        }
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = Error;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidTemplate(template.to_owned());
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let Some(start) = rest.find(['{', '}']) else {
+                parts.push(Part::Text(rest.to_owned()));
+                break;
+            };
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+            rest = &rest[start..];
+
+            let end = rest.find('}').filter(|_| rest.starts_with('{'));
+            let name = end.map(|end| &rest[1..end]).ok_or_else(invalid)?;
+            let placeholder = Placeholder::ALL
+                .into_iter()
+                .find(|placeholder| placeholder.name() == name)
+                .ok_or_else(invalid)?;
+            parts.push(Part::Placeholder(placeholder));
+            rest = &rest[name.len() + 2..];
+        }
+
+        if parts.is_empty() {
+            Err(invalid())?;
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => f.write_str(text)?,
+                Part::Placeholder(placeholder) => write!(f, "{{{}}}", placeholder.name())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that `name` is a plain file name, so packages stay in the output
+/// directory.
+pub fn check_name(name: &str) -> crate::error::Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(['/', '\\']) => Ok(()),
+        _ => Err(Error::InvalidName(name.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NameTemplate, NameValues, check_name};
+    use crate::error::Error;
+
+    #[test]
+    fn render() {
+        let template: NameTemplate = "{contest}-{stem}".parse().unwrap();
+        assert!(!template.uses_id());
+        assert_eq!(template.to_string(), "{contest}-{stem}");
+
+        let values = NameValues {
+            stem: "A",
+            contest: Some("day1"),
+            id: None,
+        };
+        assert_eq!(template.render(&values).unwrap(), "day1-A");
+
+        let template: NameTemplate = "p{id}".parse().unwrap();
+        assert!(template.uses_id());
+        assert!(matches!(
+            template.render(&values),
+            Err(Error::MissingName("id"))
+        ));
+        let values = NameValues {
+            id: Some("1001"),
+            ..values
+        };
+        assert_eq!(template.render(&values).unwrap(), "p1001");
+
+        let values = NameValues {
+            stem: "..",
+            ..values
+        };
+        let template: NameTemplate = "{stem}".parse().unwrap();
+        assert!(matches!(
+            template.render(&values),
+            Err(Error::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_templates() {
+        for template in ["", "{", "}", "{name}", "{stem", "a}b", "{{stem}}"] {
+            assert!(
+                matches!(
+                    template.parse::<NameTemplate>(),
+                    Err(Error::InvalidTemplate(_))
+                ),
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert!(check_name("config").is_ok());
+        assert!(check_name("day1-A").is_ok());
+        for name in ["", ".", "..", "a/b", "/a", "a\\b"] {
+            assert!(check_name(name).is_err(), "{name}");
+        }
+    }
+}